@@ -1,52 +1,349 @@
+use async_std::future;
 use async_std::task;
 use async_trait::async_trait;
+use cucumber::writer::Failure;
 use cucumber::{given, then, when, WorldInit};
+use futures::channel::mpsc;
+use futures::StreamExt;
 use gstreamer::glib;
 use gstreamer::prelude::*;
 use once_cell::sync::Lazy;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::cmp;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::convert::Infallible;
 use std::path::Path;
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
+use std::time::Instant;
 use std::time::SystemTime;
 
 #[cfg(feature = "validate")]
 use gstreamer_validate::prelude::*;
 
+#[cfg(feature = "controller")]
+use gstreamer_controller::prelude::*;
+
+#[cfg(feature = "junit")]
+use cucumber::WriterExt;
+
 use gstreamer as gst;
+use gstreamer_video as gstvideo;
+
+#[cfg(feature = "audio")]
+use gstreamer_audio as gstaudio;
 
 #[cfg(feature = "validate")]
 use gstreamer_validate as gstvalidate;
 
+#[cfg(feature = "controller")]
+use gstreamer_controller as gstcontroller;
+
 static CAT: Lazy<gst::DebugCategory> =
     Lazy::new(|| gst::DebugCategory::new("cucumber", gst::DebugColorFlags::empty(), Some("🥒")));
 
+/// Environment variable the random number generator of scenarios gets seeded
+/// from, so that randomized scenarios can be replayed.
+const RANDOM_SEED_ENV: &str = "GST_CUCUMBER_RANDOM_SEED";
+
+/// Environment variable holding the path of a JUnit XML report to write, next
+/// to the console output, when the `junit` feature is enabled.
+#[cfg(feature = "junit")]
+const JUNIT_OUTPUT_ENV: &str = "GST_CUCUMBER_JUNIT_OUTPUT";
+
+/// Prefix of the temporary gst-validate configuration files.
+#[cfg(feature = "validate")]
+const VALIDATE_CONFIG_PREFIX: &str = "gst-cucumber-validate-";
+
+/// Default time steps wait for data to flow through the pipeline before failing.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time to wait for the pipeline to drain when stopping it before giving up.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Number of times each property gets set when setting properties concurrently.
+const CONCURRENT_PROPERTY_SETS: usize = 100;
+
+/// Number of buffers gathered by steps checking properties over a stream of buffers.
+const PROBED_BUFFERS_COUNT: usize = 30;
+
+/// Interval at which queue levels get sampled.
+const QUEUE_SAMPLING_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Size of the ring buffer `queue2` elements use in timeshift buffering mode.
+const TIMESHIFT_RING_BUFFER_SIZE: u64 = 16 << 20;
+
+/// Maximum relative difference accepted when comparing processing rates.
+const RATE_TOLERANCE: f64 = 0.1;
+
+/// Maximum difference accepted when comparing the pipeline position.
+const POSITION_TOLERANCE: gst::ClockTime = gst::ClockTime::from_mseconds(100);
+
+/// Maximum difference accepted when comparing buffer timestamps.
+const TIMESTAMP_TOLERANCE: gst::ClockTime = gst::ClockTime::from_mseconds(1);
+
+/// Minimum PSNR, in dB, for a frame to be considered matching its reference.
+const MIN_PSNR: f64 = 35.;
+
 #[cfg(feature = "validate")]
 #[derive(Debug)]
 struct Validate {
     runner: Option<gstvalidate::Runner>,
     monitor: Option<gstvalidate::Monitor>,
     validateconfig: Option<tempfile::NamedTempFile>,
+    /// Configuration file kept on disk for gst-validate to load, removed when
+    /// the scenario ends.
+    config_path: Option<PathBuf>,
+    /// gst-validate scenario to run once activated.
+    scenario: Option<String>,
+}
+
+/// A property animated by a control binding.
+#[cfg(feature = "controller")]
+#[derive(Debug)]
+struct Animation {
+    from: f64,
+    to: f64,
+    start: Instant,
+    duration: Duration,
+}
+
+/// Counts the emissions of a signal on an element.
+#[derive(Debug)]
+struct SignalCounter {
+    element: gst::Element,
+    handler_id: glib::SignalHandlerId,
+    count: Arc<AtomicUsize>,
+}
+
+/// Periodically records a value from an async task, until stopped.
+#[derive(Debug)]
+struct Sampler {
+    stop: Arc<AtomicBool>,
+    /// Sampled values, along with the time elapsed since sampling started.
+    samples: Arc<Mutex<Vec<(Duration, f64)>>>,
+}
+
+impl Sampler {
+    fn start<F>(interval: Duration, sample: F) -> Self
+    where
+        F: Fn() -> f64 + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let samples = Arc::new(Mutex::new(Vec::new()));
+
+        task::spawn({
+            let stop = stop.clone();
+            let samples = samples.clone();
+            async move {
+                let start = Instant::now();
+                while !stop.load(Ordering::SeqCst) {
+                    samples.lock().unwrap().push((start.elapsed(), sample()));
+                    task::sleep(interval).await;
+                }
+            }
+        });
+
+        Self { stop, samples }
+    }
+
+    fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Step whose use in a scenario gets GLib criticals and warnings recorded, see
+/// `check_no_glib_criticals()`.
+const GLIB_CRITICALS_STEP: &str = "No GLib criticals are logged";
+
+/// State of the process-wide GLib log handler recording criticals and
+/// warnings for the worlds whose scenario checks them.
+#[derive(Default)]
+struct GLibLogRecording {
+    /// Where each recording world collects the logged messages.
+    sinks: Vec<Arc<Mutex<Vec<String>>>>,
+    /// Default handler set before the recording one got installed, chained up
+    /// to and restored once no world records anymore.
+    previous_handler: glib::ffi::GLogFunc,
+}
+
+static GLIB_LOG_RECORDING: Lazy<Mutex<GLibLogRecording>> = Lazy::new(Default::default);
+
+/// GLib default log handler dispatching criticals and warnings to all the
+/// recording worlds, still handing every message to the previous handler.
+unsafe extern "C" fn record_glib_log(
+    domain: *const std::os::raw::c_char,
+    level: glib::ffi::GLogLevelFlags,
+    message: *const std::os::raw::c_char,
+    user_data: glib::ffi::gpointer,
+) {
+    let previous_handler = {
+        let recording = GLIB_LOG_RECORDING.lock().unwrap();
+        let level_name = if level & glib::ffi::G_LOG_LEVEL_CRITICAL != 0 {
+            Some("Critical")
+        } else if level & glib::ffi::G_LOG_LEVEL_WARNING != 0 {
+            Some("Warning")
+        } else {
+            None
+        };
+
+        if let Some(level_name) = level_name {
+            let domain = if domain.is_null() {
+                "default".into()
+            } else {
+                std::ffi::CStr::from_ptr(domain).to_string_lossy()
+            };
+            let text = if message.is_null() {
+                "(NULL)".into()
+            } else {
+                std::ffi::CStr::from_ptr(message).to_string_lossy()
+            };
+            for sink in &recording.sinks {
+                sink.lock()
+                    .unwrap()
+                    .push(format!("{}-{}: {}", domain, level_name, text));
+            }
+        }
+
+        recording.previous_handler
+    };
+
+    if let Some(previous_handler) = previous_handler {
+        previous_handler(domain, level, message, user_data);
+    }
 }
 
+/// Fail if any step, hook or feature file parsing failed in the run reported
+/// by `writer`.
+fn check_execution<W: Failure<World>>(writer: &W) -> Result<(), anyhow::Error> {
+    if writer.execution_has_failed() {
+        anyhow::bail!(
+            "{} steps failed, {} parsing errors, {} hook errors",
+            writer.failed_steps(),
+            writer.parsing_errors(),
+            writer.hook_errors()
+        );
+    }
+
+    Ok(())
+}
+
+/// The `Cucumber` executor `World::run_with()` lets applications customize.
+pub type DefaultCucumber<I> = cucumber::Cucumber<
+    World,
+    cucumber::parser::Basic,
+    I,
+    cucumber::runner::Basic<World>,
+    cucumber::writer::Summarize<cucumber::writer::Normalize<World, cucumber::writer::Basic>>,
+>;
+
 #[derive(Debug, WorldInit)]
 pub struct World {
     pipeline: Option<gst::Element>,
 
+    /// Description the pipeline was created from, if any.
+    pipeline_description: Option<String>,
+
+    /// Additional pipelines, indexed by name, for scenarios involving several
+    /// pipelines such as network streaming ones.
+    named_pipelines: HashMap<String, gst::Element>,
+
     #[cfg(feature = "validate")]
     validate: Validate,
 
     current_feature_path: Option<PathBuf>,
 
+    /// Messages posted on the pipeline bus, recorded from a sync handler.
+    messages: Arc<Mutex<Vec<gst::Message>>>,
+
+    /// Running time of the pipeline when each message of `messages` got posted.
+    message_running_times: Arc<Mutex<Vec<Option<gst::ClockTime>>>>,
+
+    /// Tags posted on the pipeline bus, merged in posting order.
+    tags: Arc<Mutex<gst::TagList>>,
+
+    /// Contexts prepared with `add_context()`, indexed by context type.
+    contexts: HashMap<String, gst::Context>,
+
+    /// Contexts set on the elements requesting them from need-context
+    /// messages, indexed by context type.
+    provided_contexts: Arc<Mutex<HashMap<String, gst::Context>>>,
+
+    /// Probes installed by steps, removed when the scenario ends.
+    probes: Vec<(gst::Pad, gst::PadProbeId)>,
+
+    /// Signal counters, indexed by element and signal names. Handlers get
+    /// disconnected when the scenario ends.
+    signal_counters: HashMap<(String, String), SignalCounter>,
+
+    /// Samplers, indexed by the name of what they sample. Sampling tasks get
+    /// stopped when the scenario ends.
+    samplers: HashMap<String, Sampler>,
+
+    /// Animated properties, indexed by property specifier.
+    #[cfg(feature = "controller")]
+    animations: HashMap<String, Animation>,
+
+    /// Name of the last preset loaded on each element.
+    loaded_presets: HashMap<String, String>,
+
+    /// Last samples seen by each element being recorded, oldest first.
+    recorded_samples: HashMap<String, Arc<Mutex<VecDeque<gst::Sample>>>>,
+
+    /// Request pads of the pipeline elements before it last left the NULL
+    /// state, such as the ones requested while building it. Only the pads
+    /// requested afterwards are expected to be released when stopping it.
+    initial_request_pads: Mutex<HashSet<gst::Pad>>,
+
+    /// GLib critical and warning messages logged during the scenario.
+    glib_warnings: Arc<Mutex<Vec<String>>>,
+
+    /// Seed of `rng`, taken from `GST_CUCUMBER_RANDOM_SEED` when set.
+    random_seed: u64,
+
+    /// Random number generator used by randomized steps.
+    rng: StdRng,
+
     /// Information that can be gathered with additional Gherkin steps for third-party scenarios.
     pub extra_data: gst::Structure,
 }
 
 impl Drop for World {
     fn drop(&mut self) {
-        let _ = self.set_pipeline_state("stop".to_string());
+        let _ = self.stop_pipelines_blocking();
+
+        for (pad, probe_id) in self.probes.drain(..) {
+            pad.remove_probe(probe_id);
+        }
+
+        for (_, counter) in self.signal_counters.drain() {
+            counter.element.disconnect(counter.handler_id);
+        }
+
+        for (_, sampler) in self.samplers.drain() {
+            sampler.stop();
+        }
+
+        #[cfg(feature = "validate")]
+        if let Some(path) = self.validate.config_path.take() {
+            let _ = std::fs::remove_file(path);
+            std::env::remove_var("GST_VALIDATE_CONFIG");
+        }
+
+        #[cfg(feature = "validate")]
+        if self.validate.scenario.take().is_some() {
+            std::env::remove_var("GST_VALIDATE_SCENARIO");
+        }
+
+        self.stop_recording_glib_log();
     }
 }
 
@@ -54,37 +351,219 @@ impl World {
     /// Main entry point for the test harness. Input is the path to a Gherkin
     /// .feature file defining the scenario to run. `extra_data` is an optional
     /// storage that will store data gathered from additional test steps.
+    ///
+    /// Panics when any scenario failed, see `run_checked()` to handle failures
+    /// instead.
+    ///
+    /// With the `junit` feature enabled, a JUnit XML report is also written to
+    /// the path set in the `GST_CUCUMBER_JUNIT_OUTPUT` environment variable.
     pub async fn run<I>(input: I, extra_data: Option<gst::Structure>)
     where
         I: AsRef<Path>,
+    {
+        if let Err(err) = Self::run_checked(input, extra_data).await {
+            panic!("{}", err);
+        }
+    }
+
+    /// Same as `run()`, letting `configure` customize the `Cucumber` executor
+    /// before running it, e.g. to add third-party steps with its `given()`,
+    /// `when()` and `then()` methods next to the built-in ones.
+    pub async fn run_with<I, F>(input: I, extra_data: Option<gst::Structure>, configure: F)
+    where
+        I: AsRef<Path>,
+        F: FnOnce(DefaultCucumber<I>) -> DefaultCucumber<I>,
+    {
+        if let Err(err) = Self::run_collecting(input, extra_data, configure).await {
+            panic!("{}", err);
+        }
+    }
+
+    /// Same as `run()`, but returning an error when any scenario failed rather
+    /// than panicking, so that several feature files can be run from the same
+    /// test binary.
+    pub async fn run_checked<I>(
+        input: I,
+        extra_data: Option<gst::Structure>,
+    ) -> Result<(), anyhow::Error>
+    where
+        I: AsRef<Path>,
+    {
+        Self::run_collecting(input, extra_data, |cucumber| cucumber)
+            .await
+            .map(|_| ())
+    }
+
+    /// Run the scenarios of the given .feature file twice and check both runs
+    /// gathered the same `extra_data` (checksums, counts...), scenario by
+    /// scenario, verifying the pipelines are deterministic. All `extra_data`
+    /// fields take part in the comparison, except the ones listed,
+    /// comma-separated, in the `ignored-keys` string field of the given
+    /// `extra_data`, e.g. `ignored-keys="sink-realtime-factor"`. Fails if any
+    /// scenario failed.
+    pub async fn run_twice_and_compare<I>(
+        input: I,
+        extra_data: Option<gst::Structure>,
+    ) -> Result<(), anyhow::Error>
+    where
+        I: AsRef<Path>,
+    {
+        let ignored_keys = extra_data
+            .as_ref()
+            .and_then(|d| d.get::<String>("ignored-keys").ok())
+            .map(|keys| {
+                keys.split(',')
+                    .map(|key| key.trim().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        let first = Self::run_collecting(&input, extra_data.clone(), |cucumber| cucumber).await?;
+        let second = Self::run_collecting(&input, extra_data, |cucumber| cucumber).await?;
+        if first.len() != second.len() {
+            anyhow::bail!(
+                "First run ran {} scenarios, second run {}",
+                first.len(),
+                second.len()
+            );
+        }
+
+        for (i, (first, second)) in first.iter().zip(second.iter()).enumerate() {
+            for (key, value) in first.iter() {
+                if ignored_keys.iter().any(|ignored| ignored == key) {
+                    continue;
+                }
+
+                match second.value(key) {
+                    Ok(other) if value.compare(other) == Some(cmp::Ordering::Equal) => (),
+                    other => anyhow::bail!(
+                        "Scenario #{} diverged on {}: {:?} then {:?}",
+                        i,
+                        key,
+                        value,
+                        other.ok()
+                    ),
+                }
+            }
+
+            if let Some(key) = second
+                .fields()
+                .find(|key| !first.has_field(key) && !ignored_keys.iter().any(|k| k == key))
+            {
+                anyhow::bail!("Scenario #{} only gathered {} in its second run", i, key);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the scenarios of the given .feature file, with the `Cucumber`
+    /// executor customized by `configure`, returning the `extra_data` gathered
+    /// by each of them.
+    async fn run_collecting<I, F>(
+        input: I,
+        extra_data: Option<gst::Structure>,
+        configure: F,
+    ) -> Result<Vec<gst::Structure>, anyhow::Error>
+    where
+        I: AsRef<Path>,
+        F: FnOnce(DefaultCucumber<I>) -> DefaultCucumber<I>,
     {
         let extra_data = Arc::new(extra_data);
-        Self::cucumber()
+        let snapshots = Arc::new(Mutex::new(Vec::new()));
+
+        let executor = configure(Self::cucumber())
             .max_concurrent_scenarios(1)
-            .before(move |feature, _, _scenario, world| {
-                let edata = extra_data.clone();
-                if let Some(d) = edata.as_ref() {
+            .before(move |feature, rule, scenario, world| {
+                if let Some(d) = extra_data.as_ref() {
                     world.extra_data = d.clone();
                 }
                 world.current_feature_path = feature.path.clone();
 
+                let steps = feature
+                    .background
+                    .iter()
+                    .chain(rule.and_then(|rule| rule.background.as_ref()))
+                    .flat_map(|background| &background.steps);
+                if steps
+                    .chain(&scenario.steps)
+                    .any(|step| step.value == GLIB_CRITICALS_STEP)
+                {
+                    world.record_glib_log();
+                }
+
                 Box::pin(async move {
                     gst::info!(CAT, "Before: {:?} {:?}", feature, world);
                 })
             })
-            .after(|_, _, _, _world| {
-                Box::pin(async move {
-                    #[cfg(feature = "validate")]
-                    if let Some(world) = _world.as_ref() {
-                        if let Some(runner) = &world.validate.runner {
-                            let res = runner.exit(true);
-                            debug_assert!(res == 0, "Reported issues: {:?}", runner.reports());
-                        }
+            .after({
+                let snapshots = snapshots.clone();
+                move |_, _, _, world| {
+                    if let Some(world) = world.as_ref() {
+                        snapshots.lock().unwrap().push(world.extra_data.clone());
                     }
-                })
-            })
-            .run_and_exit(input)
-            .await
+
+                    Box::pin(async move {
+                        if let Some(world) = world {
+                            world.finish_scenario();
+                        }
+                    })
+                }
+            });
+
+        #[cfg(feature = "junit")]
+        if let Some(path) = std::env::var_os(JUNIT_OUTPUT_ENV) {
+            let output = std::fs::File::create(&path).map_err(|err| {
+                anyhow::anyhow!("Could not create {}: {}", Path::new(&path).display(), err)
+            })?;
+            let writer = executor
+                .with_writer(
+                    cucumber::writer::Basic::raw(
+                        std::io::stdout(),
+                        cucumber::writer::Coloring::Auto,
+                        0,
+                    )
+                    .summarized()
+                    .tee::<World, _>(cucumber::writer::JUnit::for_tee(output, 0))
+                    .normalized(),
+                )
+                .run(input)
+                .await;
+            check_execution(&writer)?;
+
+            let snapshots = snapshots.lock().unwrap().clone();
+            return Ok(snapshots);
+        }
+
+        let writer = executor.run(input).await;
+        check_execution(&writer)?;
+
+        let snapshots = snapshots.lock().unwrap().clone();
+        Ok(snapshots)
+    }
+
+    /// Wrap up a scenario that just ran, checking gst-validate didn't report
+    /// issues when it was activated.
+    fn finish_scenario(&self) {
+        #[cfg(feature = "validate")]
+        if let Some(runner) = &self.validate.runner {
+            let res = runner.exit(true);
+            debug_assert!(res == 0, "Reported issues: {:?}", runner.reports());
+        }
+    }
+
+    /// Register the element type `T` under the factory name `name`, so that
+    /// test-only elements implemented in the test binary can be referenced from
+    /// pipeline descriptions. Registration is process-wide: the factory remains
+    /// available to all the scenarios run afterwards and can't be unregistered.
+    /// GStreamer needs to be initialized first.
+    pub fn register_element<T>(name: &str, rank: gst::Rank) -> Result<(), anyhow::Error>
+    where
+        T: IsA<gst::Element>,
+    {
+        gst::debug!(CAT, "Registering {} as {}", T::static_type(), name);
+        gst::Element::register(None, name, rank, T::static_type())?;
+        Ok(())
     }
 
     /// Create the pipeline based on the given GStreamer parse-launch
@@ -99,15 +578,109 @@ impl World {
         pipeline_description: String,
     ) -> Result<(), anyhow::Error> {
         gst::debug!(CAT, "Pipeline is: '{}'", pipeline_description);
-        self.pipeline = Some(gst::parse_launch(&pipeline_description)?);
+        self.set_pipeline(gst::parse_launch(&pipeline_description)?);
+        self.pipeline_description = Some(pipeline_description);
         Ok(())
     }
 
     /// Set the pipeline from an already created GStreamer pipeline. This can be
     /// used for dynamic pipelines, directly involving `decodebin` GStreamer
     /// elements for instance.
+    ///
+    /// All messages posted on the pipeline bus get recorded from a sync
+    /// handler, which replaces any sync handler previously set on that bus.
+    /// Recorded messages are dropped from the bus, so bus watches and
+    /// `pop()` calls of the application don't see them.
     pub fn set_pipeline(&mut self, pipeline: gst::Element) {
+        self.record_messages(&pipeline);
         self.pipeline = Some(pipeline);
+        self.pipeline_description = None;
+    }
+
+    /// Add a pipeline identified by `name`, next to the main one. Named
+    /// pipelines follow the state changes of the main pipeline and their bus
+    /// messages get recorded along with its ones.
+    pub fn set_named_pipeline(&mut self, name: &str, pipeline: gst::Element) {
+        self.record_messages(&pipeline);
+        self.named_pipelines.insert(name.to_string(), pipeline);
+    }
+
+    /// Named pipeline accessor, see `set_named_pipeline()`.
+    pub fn get_named_pipeline(&self, name: &str) -> Result<&gst::Element, anyhow::Error> {
+        self.named_pipelines
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("No pipeline named {}", name))
+    }
+
+    /// Prepare a context, e.g. a shared display, to be provided to elements
+    /// requesting it once the `I provide context '...' on need-context` step
+    /// enabled it.
+    pub fn add_context(&mut self, context: gst::Context) {
+        self.contexts
+            .insert(context.context_type().to_string(), context);
+    }
+
+    /// Record the messages posted on the bus of `pipeline` from a sync handler,
+    /// merging the tags they carry and answering need-context messages with
+    /// the provided contexts. The handler previously set on the bus, if any,
+    /// is removed first, as GStreamer refuses to replace it. Recorded messages
+    /// are dropped rather than queued on the bus, where nothing pops them.
+    fn record_messages(&self, pipeline: &gst::Element) {
+        if let Some(bus) = pipeline.bus() {
+            bus.unset_sync_handler();
+            let messages = self.messages.clone();
+            let running_times = self.message_running_times.clone();
+            let provided_contexts = self.provided_contexts.clone();
+            let tags = self.tags.clone();
+            let pipeline = pipeline.downgrade();
+            bus.set_sync_handler(move |_, msg| {
+                let running_time = pipeline
+                    .upgrade()
+                    .and_then(|pipeline| pipeline.current_running_time());
+
+                if let gst::MessageView::Tag(tag) = msg.view() {
+                    tags.lock()
+                        .unwrap()
+                        .make_mut()
+                        .insert(&tag.tags(), gst::TagMergeMode::Replace);
+                }
+
+                if let gst::MessageView::NeedContext(need) = msg.view() {
+                    let element = msg
+                        .src()
+                        .and_then(|src| src.downcast::<gst::Element>().ok());
+                    if let (Some(element), Some(context)) = (
+                        element,
+                        provided_contexts.lock().unwrap().get(need.context_type()),
+                    ) {
+                        gst::debug!(
+                            CAT,
+                            "Providing {} context to {}",
+                            need.context_type(),
+                            element.name()
+                        );
+                        element.set_context(context);
+                    }
+                }
+
+                let mut messages = messages.lock().unwrap();
+                running_times.lock().unwrap().push(running_time);
+                messages.push(msg.clone());
+                gst::BusSyncReply::Drop
+            });
+        }
+    }
+
+    /// Reports gathered by gst-validate since it got activated, useful to check
+    /// for specific issues from third-party Gherkin steps. Empty when validate
+    /// isn't activated.
+    #[cfg(feature = "validate")]
+    pub fn validate_reports(&self) -> Vec<gstvalidate::Report> {
+        self.validate
+            .runner
+            .as_ref()
+            .map(|runner| runner.reports())
+            .unwrap_or_default()
     }
 
     /// Pipeline accessor, useful for interacting with the pipeline (sending
@@ -121,10 +694,8 @@ impl World {
     /// Changes the pipeline state, supported values for `state` are `stop`,
     /// `prepare`, `pause` and `play`. When stopping we make sure emit an EOS
     /// event, ensuring all elements have handled it and cleaned up their
-    /// internal state properly.
-    fn set_pipeline_state(&self, state: String) -> Result<(), anyhow::Error> {
-        let pipeline = self.get_pipeline()?;
-
+    /// internal state properly. Named pipelines follow the same state changes.
+    async fn set_pipeline_state(&self, state: String) -> Result<(), anyhow::Error> {
         let target_state = match state.as_str() {
             "stop" => gst::State::Null,
             "prepare" => gst::State::Ready,
@@ -133,299 +704,5130 @@ impl World {
             _ => panic!("Invalid state name: {}", state),
         };
 
-        if target_state == gst::State::Null {
-            let (_success, current, _pending) = pipeline.state(gst::ClockTime::NONE);
-            if current == target_state {
-                return Ok(());
-            }
+        if target_state != gst::State::Null
+            && self.get_pipeline()?.current_state() == gst::State::Null
+        {
+            *self.initial_request_pads.lock().unwrap() = self
+                .pipeline_elements()?
+                .iter()
+                .flat_map(request_pads)
+                .collect();
+        }
 
-            // gst-validate expects the EOS event to be matched with a previous flush sequence (?).
-            let flush = if cfg!(feature = "validate") {
-                true
-            } else {
-                false
-            };
+        for pipeline in self.pipelines()? {
+            self.change_state(pipeline, target_state).await?;
+        }
 
-            let seqnum = gst::event::Seqnum::next();
-            if flush {
-                pipeline.send_event(gst::event::FlushStart::new());
-                pipeline.send_event(gst::event::FlushStop::builder(true).seqnum(seqnum).build());
-            }
+        Ok(())
+    }
 
-            // Send EOS event and wait until all sinks have received it.
-            pipeline.send_event(gst::event::Eos::builder().seqnum(seqnum).build());
-
-            let bus = pipeline.bus().unwrap();
-            for msg in bus.iter_timed(gst::ClockTime::NONE) {
-                use gst::MessageView;
-
-                match msg.view() {
-                    MessageView::Eos(..) => break,
-                    MessageView::Error(err) => {
-                        eprintln!(
-                            "Error from {:?}: {} ({:?})",
-                            err.src().map(|s| s.path_string()),
-                            err.error(),
-                            err.debug()
-                        );
-                        break;
+    /// Same as `set_pipeline_state("stop")`, blocking the calling thread
+    /// while draining the pipelines, for when the scenario ends.
+    fn stop_pipelines_blocking(&self) -> Result<(), anyhow::Error> {
+        for pipeline in self.pipelines()? {
+            if let Some((since, seqnum)) = self.start_drain(pipeline) {
+                let start = Instant::now();
+                let drained = loop {
+                    let msg = self.find_message_since(since, |msg| Self::ends_drain(msg, seqnum));
+                    if msg.is_some() || start.elapsed() >= STOP_TIMEOUT {
+                        break msg;
                     }
-                    _ => (),
-                }
+
+                    std::thread::sleep(Duration::from_millis(50));
+                };
+                Self::check_drained(pipeline, drained)?;
             }
+
+            Self::apply_state(pipeline, gst::State::Null)?;
         }
 
-        pipeline
-            .set_state(target_state)
-            .map(|_| ())
-            .map_err(|_| anyhow::anyhow!("Unable to set pipeline state"))
+        Ok(())
     }
 
-    fn find_element_property(
-        &self,
-        propname: &str,
-    ) -> Result<(glib::ParamSpec, glib::Object), anyhow::Error> {
-        let pipeline = self.get_pipeline()?;
-        let tokens = propname.split("::");
-        let mut pspec = None::<glib::ParamSpec>;
-        let mut obj = None::<glib::Object>;
+    /// The main pipeline followed by the named ones.
+    fn pipelines(&self) -> Result<Vec<&gst::Element>, anyhow::Error> {
+        if self.pipeline.is_none() && self.named_pipelines.is_empty() {
+            anyhow::bail!("Pipeline not configured yet");
+        }
 
-        for token in tokens {
-            match obj {
-                Some(o) => {
-                    debug_assert!(pspec.is_none(), "Invalid property specifier {}", propname);
-                    pspec = o
-                        .find_property(token)
-                        .or_else(|| panic!("Couldn't find element {}", token));
+        Ok(self
+            .pipeline
+            .iter()
+            .chain(self.named_pipelines.values())
+            .collect())
+    }
 
-                    let tmpspec = pspec.unwrap().clone();
-                    if tmpspec.value_type() == glib::Object::static_type() {
-                        obj = Some(o.property::<glib::Object>(token));
-                        pspec = None;
-                    } else {
-                        obj = Some(o.clone());
-                        pspec = Some(tmpspec);
-                    }
-                }
-                None => {
-                    obj = pipeline
-                        .downcast_ref::<gst::Bin>()
-                        .unwrap()
-                        .by_name(token)
-                        .map_or_else(
-                            || panic!("Couldn't find element {}", token),
-                            |v| Some(v.upcast()),
-                        );
-                }
+    /// Change the state of `pipeline`, draining it with an EOS event first
+    /// when stopping it. Fails if the pipeline is not drained within
+    /// `STOP_TIMEOUT`, it is still stopped in that case.
+    async fn change_state(
+        &self,
+        pipeline: &gst::Element,
+        target_state: gst::State,
+    ) -> Result<(), anyhow::Error> {
+        if target_state == gst::State::Null {
+            if let Some((since, seqnum)) = self.start_drain(pipeline) {
+                let drained = self
+                    .wait_for_message_since(since, STOP_TIMEOUT, |msg| {
+                        Self::ends_drain(msg, seqnum)
+                    })
+                    .await;
+                Self::check_drained(pipeline, drained)?;
             }
         }
 
-        match (pspec, obj) {
-            (Some(pspec), Some(obj)) => Ok((pspec, obj)),
-            _ => panic!("Couldn't find object property: {}", propname),
-        }
+        Self::apply_state(pipeline, target_state)
     }
-}
 
-#[async_trait(?Send)]
-impl cucumber::World for World {
-    type Error = Infallible;
+    /// Send an EOS event to `pipeline` so that all elements handle it before
+    /// stopping, returning the index of the first message to consider and the
+    /// seqnum of the EOS, or `None` if the pipeline is already stopped.
+    fn start_drain(&self, pipeline: &gst::Element) -> Option<(usize, gst::Seqnum)> {
+        if pipeline.current_state() == gst::State::Null {
+            return None;
+        }
 
-    async fn new() -> Result<Self, Self::Error> {
-        #[cfg(feature = "validate")]
-        let validate = Validate {
-            runner: None,
-            monitor: None,
-            validateconfig: None,
-        };
+        // gst-validate expects the EOS event to be matched with a previous flush sequence (?).
+        let flush = cfg!(feature = "validate");
 
-        Ok(Self {
-            pipeline: None,
-            #[cfg(feature = "validate")]
-            validate,
-            current_feature_path: None,
-            extra_data: gst::Structure::new_empty("extra"),
-        })
+        let since = self.messages.lock().unwrap().len();
+        let seqnum = gst::event::Seqnum::next();
+        if flush {
+            pipeline.send_event(gst::event::FlushStart::new());
+            pipeline.send_event(gst::event::FlushStop::builder(true).seqnum(seqnum).build());
+        }
+
+        // Send EOS event, all sinks have received it once the pipeline posts
+        // an EOS message with the same seqnum.
+        pipeline.send_event(gst::event::Eos::builder().seqnum(seqnum).build());
+
+        Some((since, seqnum))
     }
-}
 
-#[given(regex = r"Pipeline is '(.*)'$")]
-fn set_pipeline(world: &mut World, pipeline: String) -> Result<(), anyhow::Error> {
+    /// Whether `msg` ends the drain started with the EOS event of `seqnum`.
+    fn ends_drain(msg: &gst::Message, seqnum: gst::Seqnum) -> bool {
+        match msg.view() {
+            gst::MessageView::Eos(..) => msg.seqnum() == seqnum,
+            gst::MessageView::Error(..) => true,
+            _ => false,
+        }
+    }
+
+    /// Check the message ending the drain of `pipeline`, `None` when it timed
+    /// out, in which case the pipeline is stopped anyway.
+    fn check_drained(
+        pipeline: &gst::Element,
+        drained: Option<gst::Message>,
+    ) -> Result<(), anyhow::Error> {
+        match drained {
+            Some(msg) => {
+                if let gst::MessageView::Error(err) = msg.view() {
+                    eprintln!(
+                        "Error from {:?}: {} ({:?})",
+                        err.src().map(|s| s.path_string()),
+                        err.error(),
+                        err.debug()
+                    );
+                }
+
+                Ok(())
+            }
+            None => {
+                let _ = pipeline.set_state(gst::State::Null);
+                anyhow::bail!(
+                    "Pipeline did not reach EOS {} seconds after stopping it",
+                    STOP_TIMEOUT.as_secs()
+                );
+            }
+        }
+    }
+
+    fn apply_state(pipeline: &gst::Element, target_state: gst::State) -> Result<(), anyhow::Error> {
+        pipeline
+            .set_state(target_state)
+            .map(|_| ())
+            .map_err(|_| anyhow::anyhow!("Unable to set pipeline state"))
+    }
+
+    /// Start recording the GLib criticals and warnings logged during the
+    /// scenario. The recording log handler is installed process-wide when the
+    /// first world starts recording, and messages logged meanwhile are
+    /// dispatched to all the recording worlds.
+    fn record_glib_log(&self) {
+        let mut recording = GLIB_LOG_RECORDING.lock().unwrap();
+        if recording
+            .sinks
+            .iter()
+            .any(|sink| Arc::ptr_eq(sink, &self.glib_warnings))
+        {
+            return;
+        }
+
+        if recording.sinks.is_empty() {
+            let previous_handler = unsafe {
+                glib::ffi::g_log_set_default_handler(Some(record_glib_log), std::ptr::null_mut())
+            };
+            recording.previous_handler =
+                previous_handler.or(Some(glib::ffi::g_log_default_handler));
+        }
+        recording.sinks.push(self.glib_warnings.clone());
+    }
+
+    /// Stop recording GLib messages, restoring the previous default log
+    /// handler if no other world records them.
+    fn stop_recording_glib_log(&self) {
+        let mut recording = GLIB_LOG_RECORDING.lock().unwrap();
+        let index = match recording
+            .sinks
+            .iter()
+            .position(|sink| Arc::ptr_eq(sink, &self.glib_warnings))
+        {
+            Some(index) => index,
+            None => return,
+        };
+
+        recording.sinks.remove(index);
+        if recording.sinks.is_empty() {
+            // The user data of the previous handler is not known, GLib's own
+            // default handler and the glib-rs ones don't use it.
+            unsafe {
+                glib::ffi::g_log_set_default_handler(
+                    recording.previous_handler.take(),
+                    std::ptr::null_mut(),
+                );
+            }
+        }
+    }
+
+    /// Fail if an error message was posted on the pipeline bus.
+    fn check_no_errors(&self) -> Result<(), anyhow::Error> {
+        self.check_no_errors_since(0)
+    }
+
+    /// Same as `check_no_errors()`, only considering the messages recorded
+    /// from index `since` of `messages` on.
+    fn check_no_errors_since(&self, since: usize) -> Result<(), anyhow::Error> {
+        for msg in self.messages.lock().unwrap().iter().skip(since) {
+            if let gst::MessageView::Error(err) = msg.view() {
+                anyhow::bail!(
+                    "Error from {:?}: {} ({:?})",
+                    err.src().map(|s| s.path_string()),
+                    err.error(),
+                    err.debug()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Wait up to `timeout` for a message matching `predicate` to be posted on
+    /// the pipeline bus. Messages posted before the call are taken into
+    /// account.
+    async fn wait_for_message<F>(&self, timeout: Duration, predicate: F) -> Option<gst::Message>
+    where
+        F: Fn(&gst::Message) -> bool,
+    {
+        self.wait_for_message_since(0, timeout, predicate).await
+    }
+
+    /// Wait up to `timeout` for a message matching `predicate` to be posted on
+    /// the pipeline bus, only considering messages recorded from index `since`,
+    /// e.g. the number of messages recorded before triggering an action.
+    async fn wait_for_message_since<F>(
+        &self,
+        since: usize,
+        timeout: Duration,
+        predicate: F,
+    ) -> Option<gst::Message>
+    where
+        F: Fn(&gst::Message) -> bool,
+    {
+        let start = Instant::now();
+        loop {
+            if let Some(msg) = self.find_message_since(since, &predicate) {
+                return Some(msg);
+            }
+
+            if start.elapsed() >= timeout {
+                return None;
+            }
+
+            task::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    /// The first message recorded from index `since` matching `predicate`.
+    fn find_message_since<F>(&self, since: usize, predicate: F) -> Option<gst::Message>
+    where
+        F: Fn(&gst::Message) -> bool,
+    {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .skip(since)
+            .find(|m| predicate(m))
+            .cloned()
+    }
+
+    /// Send a `seek` event to the pipeline and wait for it to be completed.
+    async fn seek_and_wait(&self, seek: gst::Event) -> Result<(), anyhow::Error> {
+        let since = self.messages.lock().unwrap().len();
+        if !self.get_pipeline()?.send_event(seek.clone()) {
+            anyhow::bail!("Pipeline refused the seek: {:?}", seek);
+        }
+
+        if self
+            .wait_for_message_since(since, DEFAULT_TIMEOUT, |msg| {
+                matches!(msg.view(), gst::MessageView::AsyncDone(..))
+            })
+            .await
+            .is_none()
+        {
+            anyhow::bail!(
+                "Seek not completed after {} seconds: {:?}",
+                DEFAULT_TIMEOUT.as_secs(),
+                seek
+            );
+        }
+
+        self.check_no_errors()
+    }
+
+    /// The most recent message posted on the pipeline bus matching `predicate`.
+    fn last_message<F>(&self, predicate: F) -> Option<gst::Message>
+    where
+        F: Fn(&gst::Message) -> bool,
+    {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|m| predicate(m))
+            .cloned()
+    }
+
+    /// All the elements of the pipeline, including the ones in sub-bins.
+    fn pipeline_elements(&self) -> Result<Vec<gst::Element>, anyhow::Error> {
+        Ok(self
+            .get_pipeline()?
+            .downcast_ref::<gst::Bin>()
+            .unwrap()
+            .iterate_recurse()
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect())
+    }
+
+    /// Resolve `path` relative to the directory of the feature file being run.
+    fn resolve_path(&self, path: &str) -> PathBuf {
+        match self.current_feature_path.as_ref().and_then(|p| p.parent()) {
+            Some(dir) if Path::new(path).is_relative() => dir.join(path),
+            _ => PathBuf::from(path),
+        }
+    }
+
+    /// The sink elements of the pipeline, including the ones in sub-bins.
+    fn pipeline_sinks(&self) -> Result<Vec<gst::Element>, anyhow::Error> {
+        Ok(self
+            .pipeline_elements()?
+            .into_iter()
+            .filter(|element| element.element_flags().contains(gst::ElementFlags::SINK))
+            .collect())
+    }
+
+    /// Look up an element by name in the pipeline, including its sub-bins.
+    fn find_element(&self, element_name: &str) -> Result<gst::Element, anyhow::Error> {
+        self.get_pipeline()?
+            .downcast_ref::<gst::Bin>()
+            .unwrap()
+            .by_name_recurse_up(element_name)
+            .ok_or_else(|| anyhow::anyhow!("Could not find element: {}", element_name))
+    }
+
+    /// Look up the pad named `pad_name` on the element named `element_name`.
+    fn find_pad(&self, pad_name: &str, element_name: &str) -> Result<gst::Pad, anyhow::Error> {
+        self.find_element(element_name)?
+            .static_pad(pad_name)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Element {} has no pad named {}", element_name, pad_name)
+            })
+    }
+
+    /// Look up the pad data flows through on the element named
+    /// `element_name`: its `sink` pad, or its `src` pad for source elements.
+    fn find_data_pad(&self, element_name: &str) -> Result<gst::Pad, anyhow::Error> {
+        data_pad(&self.find_element(element_name)?)
+    }
+
+    /// Send `event` on the pad named `pad_name` of the element named
+    /// `element_name`, pushing it downstream from src pads.
+    fn send_pad_event(
+        &self,
+        pad_name: &str,
+        element_name: &str,
+        event: gst::Event,
+    ) -> Result<(), anyhow::Error> {
+        let pad = self.find_pad(pad_name, element_name)?;
+        gst::debug!(CAT, "Sending {:?} on {}:{}", event, element_name, pad_name);
+
+        let handled = match pad.direction() {
+            gst::PadDirection::Src => pad.push_event(event),
+            _ => pad.send_event(event),
+        };
+        if !handled {
+            anyhow::bail!("Event not handled on {}:{}", element_name, pad_name);
+        }
+
+        Ok(())
+    }
+
+    fn find_element_property(
+        &self,
+        propname: &str,
+    ) -> Result<(glib::ParamSpec, glib::Object), anyhow::Error> {
+        let pipeline = self.get_pipeline()?;
+        let tokens = propname.split("::");
+        let mut pspec = None::<glib::ParamSpec>;
+        let mut obj = None::<glib::Object>;
+
+        for token in tokens {
+            match obj {
+                Some(o) => {
+                    if let Some(pspec) = &pspec {
+                        anyhow::bail!(
+                            "Invalid property specifier {}: {} is not an object property",
+                            propname,
+                            pspec.name()
+                        );
+                    }
+
+                    let tmpspec = o.find_property(token).ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Couldn't find property {} in property specifier {}",
+                            token,
+                            propname
+                        )
+                    })?;
+
+                    if tmpspec.value_type() == glib::Object::static_type() {
+                        obj = Some(o.property::<Option<glib::Object>>(token).ok_or_else(|| {
+                            anyhow::anyhow!(
+                                "Object property {} is not set in property specifier {}",
+                                token,
+                                propname
+                            )
+                        })?);
+                        pspec = None;
+                    } else {
+                        obj = Some(o.clone());
+                        pspec = Some(tmpspec);
+                    }
+                }
+                None => {
+                    obj = Some(
+                        pipeline
+                            .downcast_ref::<gst::Bin>()
+                            .unwrap()
+                            .by_name(token)
+                            .ok_or_else(|| {
+                                anyhow::anyhow!(
+                                    "Couldn't find element {} in property specifier {}",
+                                    token,
+                                    propname
+                                )
+                            })?
+                            .upcast(),
+                    );
+                }
+            }
+        }
+
+        match (pspec, obj) {
+            (Some(pspec), Some(obj)) => Ok((pspec, obj)),
+            _ => anyhow::bail!("Couldn't find object property: {}", propname),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl cucumber::World for World {
+    type Error = Infallible;
+
+    async fn new() -> Result<Self, Self::Error> {
+        #[cfg(feature = "validate")]
+        let validate = Validate {
+            runner: None,
+            monitor: None,
+            validateconfig: None,
+            config_path: None,
+            scenario: None,
+        };
+
+        let random_seed = std::env::var(RANDOM_SEED_ENV)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(rand::random);
+
+        Ok(Self {
+            pipeline: None,
+            pipeline_description: None,
+            named_pipelines: HashMap::new(),
+            #[cfg(feature = "validate")]
+            validate,
+            current_feature_path: None,
+            messages: Arc::new(Mutex::new(Vec::new())),
+            message_running_times: Arc::new(Mutex::new(Vec::new())),
+            tags: Arc::new(Mutex::new(gst::TagList::new())),
+            contexts: HashMap::new(),
+            provided_contexts: Arc::new(Mutex::new(HashMap::new())),
+            probes: Vec::new(),
+            signal_counters: HashMap::new(),
+            samplers: HashMap::new(),
+            #[cfg(feature = "controller")]
+            animations: HashMap::new(),
+            loaded_presets: HashMap::new(),
+            recorded_samples: HashMap::new(),
+            initial_request_pads: Mutex::new(HashSet::new()),
+            glib_warnings: Arc::new(Mutex::new(Vec::new())),
+            random_seed,
+            rng: StdRng::seed_from_u64(random_seed),
+            extra_data: gst::Structure::new_empty("extra"),
+        })
+    }
+}
+
+#[given(regex = r"Pipeline is '(.*)'$")]
+fn set_pipeline(world: &mut World, pipeline: String) -> Result<(), anyhow::Error> {
     world.set_pipeline_from_description(pipeline)
 }
 
-#[when(expr = "I wait for {word} {word}")]
-async fn wait(_w: &mut World, v: u64, unit: String) {
-    task::sleep(match unit.to_lowercase().as_str() {
-        "min" | "mins" | "minute" | "minutes" => Duration::from_secs(v * 60),
-        "sec" | "secs" | "second" | "seconds" => Duration::from_secs(v),
-        "ms" | "millisecond" | "milliseconds" => Duration::from_millis(v),
-        "us" | "microsecond" | "microseconds" => Duration::from_micros(v),
-        _ => panic!(
-            "Invalid unit: {} only [min, sec, ms, us] are supported",
-            unit
-        ),
-    })
-    .await;
+/// Load the pipeline description from a file, relative to the feature file
+/// when the path is relative. Lines starting with `#` are comments.
+#[given(expr = "Pipeline from file {string}")]
+fn set_pipeline_from_file(w: &mut World, path: String) -> Result<(), anyhow::Error> {
+    let path = w.resolve_path(&path);
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|err| anyhow::anyhow!("Could not read {}: {}", path.display(), err))?;
+
+    let description = contents
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join(" ");
+    w.set_pipeline_from_description(description)
+}
+
+#[given(regex = r"Pipeline '(\S+)' is '(.*)'$")]
+fn set_named_pipeline(w: &mut World, name: String, pipeline: String) -> Result<(), anyhow::Error> {
+    gst::debug!(CAT, "Pipeline {} is: '{}'", name, pipeline);
+    w.set_named_pipeline(&name, gst::parse_launch(&pipeline)?);
+    Ok(())
+}
+
+/// Check an element factory was registered, e.g. by the test binary with
+/// `World::register_element()`, before using it in pipeline descriptions.
+#[given(expr = "Element {word} is registered")]
+fn check_element_registered(_w: &mut World, factory_name: String) -> Result<(), anyhow::Error> {
+    if gst::ElementFactory::find(&factory_name).is_none() {
+        anyhow::bail!("No element factory named {}", factory_name);
+    }
+
+    Ok(())
+}
+
+/// Convert `value` expressed in `unit` (`min`, `sec`, `ms` or `us`) to a `Duration`.
+fn parse_duration(value: u64, unit: &str) -> Result<Duration, anyhow::Error> {
+    Ok(match unit.to_lowercase().as_str() {
+        "min" | "mins" | "minute" | "minutes" => Duration::from_secs(value * 60),
+        "sec" | "secs" | "second" | "seconds" => Duration::from_secs(value),
+        "ms" | "millisecond" | "milliseconds" => Duration::from_millis(value),
+        "us" | "microsecond" | "microseconds" => Duration::from_micros(value),
+        _ => anyhow::bail!(
+            "Invalid unit: {} only [min, sec, ms, us] are supported",
+            unit
+        ),
+    })
+}
+
+/// Convert `value` expressed in `unit` (`B`, `kB`, `KiB`, `MB`, `MiB`, `GB` or
+/// `GiB`) to a number of bytes.
+fn parse_size(value: u64, unit: &str) -> Result<u64, anyhow::Error> {
+    Ok(match unit {
+        "B" | "byte" | "bytes" => value,
+        "kB" | "KB" => value * 1_000,
+        "KiB" => value << 10,
+        "MB" => value * 1_000_000,
+        "MiB" => value << 20,
+        "GB" => value * 1_000_000_000,
+        "GiB" => value << 30,
+        _ => anyhow::bail!(
+            "Invalid unit: {} only [B, kB, KiB, MB, MiB, GB, GiB] are supported",
+            unit
+        ),
+    })
+}
+
+/// Parse a duration with its unit attached, such as `5sec` or `1500ms`, see
+/// `parse_duration()` for the supported units.
+fn parse_duration_str(duration: &str) -> Result<Duration, anyhow::Error> {
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (value, unit) = duration.split_at(split);
+    let value = value
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration: {}", duration))?;
+
+    parse_duration(value, unit)
+}
+
+#[when(expr = "I wait for {word} {word}")]
+async fn wait(_w: &mut World, v: u64, unit: String) -> Result<(), anyhow::Error> {
+    task::sleep(parse_duration(v, &unit)?).await;
+    Ok(())
+}
+
+/// Pin the seed of the random number generator used by randomized steps,
+/// making the scenario reproducible. The seed is recorded in `extra_data` as
+/// `random-seed`.
+#[given(expr = "The random seed is {word}")]
+fn set_random_seed(w: &mut World, seed: u64) {
+    gst::info!(CAT, "Random seed is {}", seed);
+    w.random_seed = seed;
+    w.rng = StdRng::seed_from_u64(seed);
+    w.extra_data.set("random-seed", seed);
+}
+
+/// Sleep for a random duration within the given range. This makes races more
+/// likely to surface when running scenarios many times; the chosen duration
+/// and the random seed are logged so that failures can be reproduced by
+/// setting `GST_CUCUMBER_RANDOM_SEED`.
+#[when(expr = "I wait for a random time between {word} and {word} {word}")]
+async fn random_wait(w: &mut World, min: u64, max: u64, unit: String) -> Result<(), anyhow::Error> {
+    if min > max {
+        anyhow::bail!("Invalid random time range: {} > {}", min, max);
+    }
+
+    let duration = w
+        .rng
+        .gen_range(parse_duration(min, &unit)?..=parse_duration(max, &unit)?);
+    gst::info!(
+        CAT,
+        "Waiting for {:?} (random seed: {})",
+        duration,
+        w.random_seed
+    );
+    task::sleep(duration).await;
+    Ok(())
+}
+
+#[when(expr = "I set property {word} to {word}")]
+fn set_property(w: &mut World, propname: String, value: String) -> Result<(), anyhow::Error> {
+    let (pspec, obj) = w.find_element_property(&propname)?;
+
+    gst::debug!(CAT, "Setting {}={}", propname, value);
+    obj.set_property_from_str(pspec.name(), &value);
+    Ok(())
+}
+
+#[then(expr = "Property {word} equals {word}")]
+fn get_property(w: &mut World, propname: String, value: String) -> Result<(), anyhow::Error> {
+    check_property_ordering(w, &propname, &value, &[cmp::Ordering::Equal], "equal to")
+}
+
+/// Compare the current value of the `propname` property to `value`,
+/// deserialized according to the property type.
+fn compare_property(
+    w: &World,
+    propname: &str,
+    value: &str,
+) -> Result<(cmp::Ordering, glib::Value), anyhow::Error> {
+    let (pspec, obj) = w.find_element_property(propname)?;
+
+    let expected = glib::Value::deserialize(value, pspec.value_type()).map_err(|_| {
+        anyhow::anyhow!(
+            "Invalid value for {} of type {}: {}",
+            propname,
+            pspec.value_type(),
+            value
+        )
+    })?;
+    let obj_value = obj.property_value(pspec.name());
+    let ordering = obj_value.compare(&expected).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Values of {} of type {} can't be compared",
+            propname,
+            pspec.value_type()
+        )
+    })?;
+
+    Ok((ordering, obj_value))
+}
+
+/// Check the `propname` property compares to `value` as one of `expected`.
+fn check_property_ordering(
+    w: &World,
+    propname: &str,
+    value: &str,
+    expected: &[cmp::Ordering],
+    relation: &str,
+) -> Result<(), anyhow::Error> {
+    let (ordering, obj_value) = compare_property(w, propname, value)?;
+    if !expected.contains(&ordering) {
+        anyhow::bail!(
+            "{}={} is not {} {}",
+            propname,
+            obj_value
+                .serialize()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|_| format!("{:?}", obj_value)),
+            relation,
+            value
+        );
+    }
+
+    Ok(())
+}
+
+#[then(expr = "Property {word} is greater than {word}")]
+fn check_property_greater(
+    w: &mut World,
+    propname: String,
+    value: String,
+) -> Result<(), anyhow::Error> {
+    check_property_ordering(
+        w,
+        &propname,
+        &value,
+        &[cmp::Ordering::Greater],
+        "greater than",
+    )
+}
+
+#[then(expr = "Property {word} is less than {word}")]
+fn check_property_less(
+    w: &mut World,
+    propname: String,
+    value: String,
+) -> Result<(), anyhow::Error> {
+    check_property_ordering(w, &propname, &value, &[cmp::Ordering::Less], "less than")
+}
+
+#[then(expr = "Property {word} is at least {word}")]
+fn check_property_at_least(
+    w: &mut World,
+    propname: String,
+    value: String,
+) -> Result<(), anyhow::Error> {
+    check_property_ordering(
+        w,
+        &propname,
+        &value,
+        &[cmp::Ordering::Greater, cmp::Ordering::Equal],
+        "at least",
+    )
+}
+
+#[then(expr = "Property {word} is at most {word}")]
+fn check_property_at_most(
+    w: &mut World,
+    propname: String,
+    value: String,
+) -> Result<(), anyhow::Error> {
+    check_property_ordering(
+        w,
+        &propname,
+        &value,
+        &[cmp::Ordering::Less, cmp::Ordering::Equal],
+        "at most",
+    )
+}
+
+/// Whether `value` is valid for `pspec` as is, GObject validation not having
+/// to modify it, e.g. to clamp it in range.
+fn is_valid_for_pspec(pspec: &glib::ParamSpec, value: &glib::Value) -> bool {
+    use glib::translate::{ToGlibPtr, ToGlibPtrMut};
+
+    let mut validated = value.clone();
+    let modified = unsafe {
+        glib::gobject_ffi::g_param_value_validate(
+            ToGlibPtr::<*mut _>::to_glib_none(pspec).0,
+            validated.to_glib_none_mut().0,
+        )
+    };
+
+    modified == glib::ffi::GFALSE
+}
+
+/// Check setting a property to an invalid value, of the wrong type or out of
+/// range, is rejected: the value can't be deserialized, GObject validation
+/// would modify it, e.g. clamping it, or the element doesn't hold it once set.
+#[then(regex = r"Setting property (\S+) to (\S+) is rejected$")]
+fn check_property_rejected(
+    w: &mut World,
+    propname: String,
+    value: String,
+) -> Result<(), anyhow::Error> {
+    let (pspec, obj) = w.find_element_property(&propname)?;
+
+    let v = match glib::Value::deserialize(&value, pspec.value_type()) {
+        Ok(v) => v,
+        Err(_) => return Ok(()),
+    };
+    if !is_valid_for_pspec(&pspec, &v) {
+        gst::debug!(CAT, "{}={} is out of the property range", propname, value);
+        return Ok(());
+    }
+
+    gst::debug!(CAT, "Trying to set {}={}", propname, value);
+    obj.set_property_from_value(pspec.name(), &v);
+    if obj.property_value(pspec.name()).compare(&v) == Some(cmp::Ordering::Equal) {
+        anyhow::bail!("{} accepted the invalid value {}", propname, value);
+    }
+
+    Ok(())
+}
+
+#[when(regex = r"I set structure property (\S+) to '(.*)'$")]
+fn set_structure_property(
+    w: &mut World,
+    propname: String,
+    value: String,
+) -> Result<(), anyhow::Error> {
+    let (pspec, obj) = w.find_element_property(&propname)?;
+
+    if pspec.value_type() != gst::Structure::static_type() {
+        anyhow::bail!(
+            "Property {} is of type {}, not GstStructure",
+            propname,
+            pspec.value_type()
+        );
+    }
+
+    let structure = gst::Structure::from_str(&value)
+        .map_err(|err| anyhow::anyhow!("Invalid structure '{}': {}", value, err))?;
+
+    gst::debug!(CAT, "Setting {}={}", propname, structure);
+    obj.set_property(pspec.name(), structure);
+    Ok(())
+}
+
+/// Parse `value` as a caps field of the given `type_`: `int`, `uint`, `int64`,
+/// `uint64`, `double`, `boolean`, `string` or `fraction` (`<num>/<den>`).
+fn parse_caps_field(type_: &str, value: &str) -> Result<glib::SendValue, anyhow::Error> {
+    let invalid = || anyhow::anyhow!("Invalid {} value: {}", type_, value);
+
+    Ok(match type_ {
+        "int" => value.parse::<i32>().map_err(|_| invalid())?.to_send_value(),
+        "uint" => value.parse::<u32>().map_err(|_| invalid())?.to_send_value(),
+        "int64" => value.parse::<i64>().map_err(|_| invalid())?.to_send_value(),
+        "uint64" => value.parse::<u64>().map_err(|_| invalid())?.to_send_value(),
+        "double" => value.parse::<f64>().map_err(|_| invalid())?.to_send_value(),
+        "boolean" => value.parse::<bool>().map_err(|_| invalid())?.to_send_value(),
+        "string" => value.to_send_value(),
+        "fraction" => {
+            let (num, den) = value
+                .split_once('/')
+                .and_then(|(num, den)| Some((num.parse().ok()?, den.parse().ok()?)))
+                .ok_or_else(invalid)?;
+            gst::Fraction::new(num, den).to_send_value()
+        }
+        _ => anyhow::bail!(
+            "Invalid type: {} only [int, uint, int64, uint64, double, boolean, string, fraction] are supported",
+            type_
+        ),
+    })
+}
+
+/// Set the `caps` property of the named element from a data table with
+/// `field | type | value` rows, see `parse_caps_field()` for the supported
+/// types. The `name` field sets the media type, e.g.:
+///
+/// ```gherkin
+/// When I set caps on capsfilter from:
+///   | name      | string   | video/x-raw |
+///   | width     | int      | 320         |
+///   | framerate | fraction | 30/1        |
+/// ```
+#[when(expr = "I set caps on {word} from:")]
+fn set_caps_from_table(
+    w: &mut World,
+    element_name: String,
+    step: &cucumber::gherkin::Step,
+) -> Result<(), anyhow::Error> {
+    let table = step
+        .table
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Missing caps data table"))?;
+
+    let mut name = None;
+    let mut fields = Vec::new();
+    for row in table.rows.iter() {
+        match row.as_slice() {
+            [field, _, _] if field == "field" => (),
+            [field, type_, value] if field == "name" => {
+                if type_ != "string" {
+                    anyhow::bail!("Invalid type for the caps name: {}", type_);
+                }
+                name = Some(value.clone());
+            }
+            [field, type_, value] => fields.push((field.clone(), parse_caps_field(type_, value)?)),
+            _ => anyhow::bail!("Invalid caps row: {:?}, expected field | type | value", row),
+        }
+    }
+
+    let name = name.ok_or_else(|| anyhow::anyhow!("Missing `name` row in caps data table"))?;
+    let mut structure = gst::Structure::new_empty(&name);
+    for (field, value) in fields {
+        structure.set_value(&field, value);
+    }
+    let caps = gst::Caps::builder_full().structure(structure).build();
+
+    let (pspec, obj) = w.find_element_property(&format!("{}::caps", element_name))?;
+    if pspec.value_type() != gst::Caps::static_type() {
+        anyhow::bail!("Property {}::caps is not of type GstCaps", element_name);
+    }
+
+    gst::debug!(CAT, "Setting caps on {} to {}", element_name, caps);
+    obj.set_property(pspec.name(), &caps);
+    Ok(())
+}
+
+#[then(expr = "Validate should not report any issue")]
+#[cfg(feature = "validate")]
+fn validate_no_reports(w: &mut World) -> Result<(), anyhow::Error> {
+    match &w.validate.runner {
+        None => debug_assert!(
+            w.validate.runner.is_some(),
+            "Validate hasn't been activated"
+        ),
+        Some(runner) => debug_assert!(
+            runner.reports_count() == 0,
+            "Reported issues: {}",
+            runner.printf()
+        ),
+    }
+
+    Ok(())
+}
+
+/// Check gst-validate reported at most `max` issues of the given level,
+/// `critical`, `warning` or `issue`, tolerating acceptable reports.
+#[then(expr = "Validate reported at most {word} issues of level {word}")]
+#[cfg(feature = "validate")]
+fn validate_reports_of_level(
+    w: &mut World,
+    max: usize,
+    level: String,
+) -> Result<(), anyhow::Error> {
+    let level = match level.as_str() {
+        "critical" => gstvalidate::ReportLevel::Critical,
+        "warning" => gstvalidate::ReportLevel::Warning,
+        "issue" => gstvalidate::ReportLevel::Issue,
+        _ => anyhow::bail!(
+            "Invalid report level: {} only [critical, warning, issue] are supported",
+            level
+        ),
+    };
+    if w.validate.runner.is_none() {
+        anyhow::bail!("Validate hasn't been activated");
+    }
+
+    let reports = w
+        .validate_reports()
+        .into_iter()
+        .filter(|report| report.level() == level)
+        .collect::<Vec<_>>();
+    if reports.len() > max {
+        anyhow::bail!(
+            "Validate reported {} issues of level {:?}, more than {}: {:?}",
+            reports.len(),
+            level,
+            max,
+            reports
+        );
+    }
+
+    Ok(())
+}
+
+#[given(regex = r"The validate configuration '(.*)'$")]
+#[cfg(feature = "validate")]
+fn add_validate_config(w: &mut World, config: String) {
+    gstvalidate::init();
+    if w.validate.validateconfig.is_none() {
+        w.validate.validateconfig = Some(
+            tempfile::Builder::new()
+                .prefix(VALIDATE_CONFIG_PREFIX)
+                .tempfile()
+                .expect("Could not create temporary file"),
+        );
+    }
+
+    use std::io::Write;
+    writeln!(w.validate.validateconfig.as_ref().unwrap(), "{}", config)
+        .expect("Couldn't write temporary config");
+}
+
+/// Check no temporary gst-validate configuration file was left behind by the
+/// previous scenarios, only the one of the current scenario may remain.
+#[then(expr = "No temporary validate files remain")]
+#[cfg(feature = "validate")]
+fn check_validate_files_removed(w: &mut World) -> Result<(), anyhow::Error> {
+    let leftovers = std::fs::read_dir(std::env::temp_dir())?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with(VALIDATE_CONFIG_PREFIX))
+                && Some(path.as_path()) != w.validate.config_path.as_deref()
+                && Some(path.as_path()) != w.validate.validateconfig.as_ref().map(|f| f.path())
+        })
+        .collect::<Vec<_>>();
+
+    if !leftovers.is_empty() {
+        anyhow::bail!("Temporary validate files remain: {:?}", leftovers);
+    }
+
+    Ok(())
+}
+
+/// Run a gst-validate scenario, such as `seek_forward` or the path of a
+/// `.scenario` file relative to the feature file, once validate gets
+/// activated. Its actions are executed while the pipeline plays.
+#[given(expr = "Validate scenario {string}")]
+#[cfg(feature = "validate")]
+fn set_validate_scenario(w: &mut World, scenario: String) -> Result<(), anyhow::Error> {
+    if w.validate.runner.is_some() {
+        anyhow::bail!("Validate scenarios need to be set before activating validate");
+    }
+
+    let scenario = if scenario.ends_with(".scenario") {
+        w.resolve_path(&scenario).display().to_string()
+    } else {
+        scenario
+    };
+    w.validate.scenario = Some(scenario);
+    Ok(())
+}
+
+#[given(expr = "Validate is activated")]
+#[cfg(feature = "validate")]
+fn activate_validate(w: &mut World) -> Result<(), anyhow::Error> {
+    debug_assert!(
+        w.validate.runner.is_none(),
+        "Validate has already been activated"
+    );
+
+    if let Some(validateconfig) = w.validate.validateconfig.take() {
+        let config_temp_path = validateconfig.into_temp_path();
+        let path = config_temp_path
+            .as_os_str()
+            .to_str()
+            .expect("Invalid config temporary file")
+            .to_string();
+        gst::debug!(CAT, "Got config: {}", &path);
+        w.validate.config_path = Some(config_temp_path.keep().expect("Could not keep config"));
+
+        std::env::set_var("GST_VALIDATE_CONFIG", path);
+    }
+
+    if let Some(scenario) = &w.validate.scenario {
+        gst::debug!(CAT, "Running validate scenario: {}", scenario);
+        std::env::set_var("GST_VALIDATE_SCENARIO", scenario);
+    }
+
+    gstvalidate::init();
+    let runner = gstvalidate::Runner::new();
+    let _ = w.validate.runner.insert(runner.clone());
+    let pipeline = w.get_pipeline()?;
+    w.validate.monitor = Some(gstvalidate::Monitor::factory_create(
+        pipeline.upcast_ref::<gst::Object>(),
+        &runner,
+        gstvalidate::Monitor::NONE,
+    ));
+    Ok(())
+}
+
+/// Change the state of the pipelines, blocking until the pipelines drained
+/// when stopping them.
+#[when(expr = "I {word} the pipeline")]
+pub fn set_state(w: &mut World, state: String) -> Result<(), anyhow::Error> {
+    task::block_on(w.set_pipeline_state(state))
+}
+
+fn get_last_frame(w: &World, element_name: &str) -> Result<Option<gst::Sample>, anyhow::Error> {
+    get_last_frame_on_element(&w.find_element(element_name)?)
+}
+
+/// Retrieve the most recent gst::Sample from the given video sink. We assume
+/// the `enable-last-sample` property is enabled on this element.
+pub fn get_last_frame_on_element(
+    element: &gst::Element,
+) -> Result<Option<gst::Sample>, anyhow::Error> {
+    let enable_last_sample = if element.find_property("enable-last-sample").is_some() {
+        element.property::<bool>("enable-last-sample")
+    } else {
+        gst::error!(CAT, "Sink doesn't have a `enable-last-sample' property");
+        false
+    };
+
+    if !enable_last_sample {
+        anyhow::bail!("Property `enable-last-sample` not `true` on: {} - you need to set it when defining the pipeline", element.name());
+    }
+
+    Ok(element.property::<Option<gst::Sample>>("last-sample"))
+}
+
+/// Wait up to `timeout` for the named sink to have prerolled a frame. The
+/// sink is polled more often for short timeouts, so that fast pipelines pass
+/// quickly.
+async fn wait_for_frame(
+    w: &World,
+    element_name: &str,
+    timeout: Duration,
+) -> Result<(), anyhow::Error> {
+    let _ = w.get_pipeline()?.state(gst::ClockTime::NONE);
+    let interval = cmp::min(Duration::from_millis(500), timeout / 10);
+
+    let start = SystemTime::now();
+    loop {
+        if get_last_frame(w, element_name)?.is_some() {
+            return Ok(());
+        }
+
+        task::sleep(interval).await;
+        if let Ok(elapsed) = start.elapsed() {
+            if elapsed >= timeout {
+                anyhow::bail!(
+                    "Timeout reached, video sink still not pre-rolled after {:?}",
+                    timeout
+                );
+            }
+        }
+    }
+}
+
+#[then(expr = "The user can see a frame on {word}")]
+async fn check_last_frame(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    wait_for_frame(w, &element_name, DEFAULT_TIMEOUT).await
+}
+
+/// Same as above with a custom timeout, e.g.
+/// `The user can see a frame on sink within 20sec` for slow hardware decoders.
+#[then(expr = "The user can see a frame on {word} within {word}")]
+async fn check_last_frame_within(
+    w: &mut World,
+    element_name: String,
+    timeout: String,
+) -> Result<(), anyhow::Error> {
+    wait_for_frame(w, &element_name, parse_duration_str(&timeout)?).await
+}
+
+/// Absolute difference between two clock times.
+fn clock_time_diff(a: gst::ClockTime, b: gst::ClockTime) -> gst::ClockTime {
+    a.max(b) - a.min(b)
+}
+
+/// Timestamps of a buffer seen by a pad probe, along with the wall-clock time
+/// it was seen at. Unlike the buffers gathered by [`probe_buffers`] it doesn't
+/// keep the buffer alive, so that buffer pools upstream are not starved while
+/// probing.
+#[derive(Debug, Clone, Copy)]
+struct BufferTiming {
+    pts: Option<gst::ClockTime>,
+    dts: Option<gst::ClockTime>,
+    arrival: Instant,
+}
+
+/// Probe `pad` and gather what `map` extracts from the first `max_buffers`
+/// buffers flowing through it, giving up after `timeout` or when EOS reaches
+/// the pad. Buffers contained in buffer lists are mapped individually.
+async fn probe_pad<T, F>(
+    pad: &gst::Pad,
+    max_buffers: usize,
+    timeout: Duration,
+    map: F,
+) -> Result<Vec<T>, anyhow::Error>
+where
+    T: Send + 'static,
+    F: Fn(&gst::BufferRef, Instant) -> T + Send + Sync + 'static,
+{
+    let (sender, mut receiver) = mpsc::unbounded();
+    let probe_id = pad
+        .add_probe(
+            gst::PadProbeType::BUFFER
+                | gst::PadProbeType::BUFFER_LIST
+                | gst::PadProbeType::EVENT_DOWNSTREAM,
+            move |_, info| {
+                let arrival = Instant::now();
+                match &info.data {
+                    Some(gst::PadProbeData::Buffer(buffer)) => {
+                        let _ = sender.unbounded_send(map(buffer, arrival));
+                    }
+                    Some(gst::PadProbeData::BufferList(list)) => {
+                        for buffer in list.iter() {
+                            let _ = sender.unbounded_send(map(buffer, arrival));
+                        }
+                    }
+                    Some(gst::PadProbeData::Event(event))
+                        if event.type_() == gst::EventType::Eos =>
+                    {
+                        sender.close_channel();
+                    }
+                    _ => (),
+                }
+
+                gst::PadProbeReturn::Ok
+            },
+        )
+        .ok_or_else(|| anyhow::anyhow!("Could not add a buffer probe on {}", pad.name()))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut items = Vec::new();
+    while items.len() < max_buffers {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match future::timeout(remaining, receiver.next()).await {
+            Ok(Some(item)) => items.push(item),
+            _ => break,
+        }
+    }
+    pad.remove_probe(probe_id);
+
+    Ok(items)
+}
+
+/// Probe `pad` and gather the first `max_buffers` buffers flowing through it,
+/// giving up after `timeout`.
+async fn probe_buffers(
+    pad: &gst::Pad,
+    max_buffers: usize,
+    timeout: Duration,
+) -> Result<Vec<gst::Buffer>, anyhow::Error> {
+    probe_pad(pad, max_buffers, timeout, |buffer, _| buffer.to_owned()).await
+}
+
+/// Same as [`probe_buffers`], only recording the timestamps of the buffers.
+async fn probe_buffer_timings(
+    pad: &gst::Pad,
+    max_buffers: usize,
+    timeout: Duration,
+) -> Result<Vec<BufferTiming>, anyhow::Error> {
+    probe_pad(pad, max_buffers, timeout, |buffer, arrival| BufferTiming {
+        pts: buffer.pts(),
+        dts: buffer.dts(),
+        arrival,
+    })
+    .await
+}
+
+#[then(expr = "The next buffer on the {word} pad of {word} has PTS {word} {word}")]
+async fn check_next_buffer_pts(
+    w: &mut World,
+    pad_name: String,
+    element_name: String,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let expected = gst::ClockTime::from_nseconds(parse_duration(value, &unit)?.as_nanos() as u64);
+    let pad = w.find_pad(&pad_name, &element_name)?;
+
+    let probed = probe_buffers(&pad, 1, DEFAULT_TIMEOUT).await?;
+    let buffer = &probed.first().ok_or_else(|| {
+        anyhow::anyhow!(
+            "No buffer flowed through {}:{} after {} seconds",
+            element_name,
+            pad_name,
+            DEFAULT_TIMEOUT.as_secs()
+        )
+    })?;
+
+    let pts = buffer
+        .pts()
+        .ok_or_else(|| anyhow::anyhow!("Buffer on {}:{} has no PTS", element_name, pad_name))?;
+    if clock_time_diff(pts, expected) > TIMESTAMP_TOLERANCE {
+        anyhow::bail!(
+            "Buffer on {}:{} has PTS {} instead of {}",
+            element_name,
+            pad_name,
+            pts,
+            expected
+        );
+    }
+
+    Ok(())
+}
+
+#[then(expr = "DTS precedes PTS on the {word} pad of {word}")]
+async fn check_dts_precedes_pts(
+    w: &mut World,
+    pad_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let pad = w.find_pad(&pad_name, &element_name)?;
+
+    let timings = probe_buffer_timings(&pad, PROBED_BUFFERS_COUNT, DEFAULT_TIMEOUT).await?;
+    if timings.is_empty() {
+        anyhow::bail!(
+            "No buffer flowed through {}:{} after {} seconds",
+            element_name,
+            pad_name,
+            DEFAULT_TIMEOUT.as_secs()
+        );
+    }
+
+    for (i, timing) in timings.iter().enumerate() {
+        if let (Some(dts), Some(pts)) = (timing.dts, timing.pts) {
+            if dts > pts {
+                anyhow::bail!(
+                    "Buffer #{} on {}:{} has DTS {} after PTS {}",
+                    i,
+                    element_name,
+                    pad_name,
+                    dts,
+                    pts
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Throttle the data flow on the sink pad of the named element. A probe
+/// blocks the streaming thread until `1 / rate` seconds passed since the
+/// previous buffer, so that upstream elements experience the element as a slow
+/// consumer. The probe is removed when the scenario ends.
+#[when(expr = "I throttle {word} to {word} buffers per second")]
+fn throttle(w: &mut World, element_name: String, rate: f64) -> Result<(), anyhow::Error> {
+    if rate <= 0. {
+        anyhow::bail!("Invalid throttling rate: {}", rate);
+    }
+
+    let pad = w.find_pad("sink", &element_name)?;
+    let interval = Duration::from_secs_f64(1. / rate);
+    let last_buffer = Mutex::new(None::<Instant>);
+    let probe_id = pad
+        .add_probe(
+            gst::PadProbeType::BUFFER | gst::PadProbeType::BUFFER_LIST,
+            move |_, _| {
+                let mut last_buffer = last_buffer.lock().unwrap();
+                if let Some(elapsed) = last_buffer.map(|last| last.elapsed()) {
+                    if elapsed < interval {
+                        std::thread::sleep(interval - elapsed);
+                    }
+                }
+                *last_buffer = Some(Instant::now());
+
+                gst::PadProbeReturn::Ok
+            },
+        )
+        .ok_or_else(|| anyhow::anyhow!("Could not add a throttling probe on {}", element_name))?;
+
+    gst::debug!(
+        CAT,
+        "Throttling {} to {} buffers per second",
+        element_name,
+        rate
+    );
+    w.probes.push((pad, probe_id));
+    Ok(())
+}
+
+/// Collect the queues found upstream of `element`.
+fn upstream_queues(element: &gst::Element) -> Vec<gst::Element> {
+    let mut queues = Vec::new();
+    let mut pending = vec![element.clone()];
+
+    while let Some(element) = pending.pop() {
+        for pad in element.sink_pads() {
+            if let Some(upstream) = pad.peer().and_then(|peer| peer.parent_element()) {
+                if upstream
+                    .factory()
+                    .is_some_and(|f| matches!(f.name().as_str(), "queue" | "queue2"))
+                {
+                    queues.push(upstream.clone());
+                }
+                pending.push(upstream);
+            }
+        }
+    }
+
+    queues
+}
+
+/// Whether any of the buffers, bytes or time limits of `queue` was reached.
+fn queue_is_full(queue: &gst::Element) -> bool {
+    let reached = |current: u64, max: u64| max > 0 && current >= max;
+
+    reached(
+        queue.property::<u32>("current-level-buffers").into(),
+        queue.property::<u32>("max-size-buffers").into(),
+    ) || reached(
+        queue.property::<u32>("current-level-bytes").into(),
+        queue.property::<u32>("max-size-bytes").into(),
+    ) || reached(
+        queue.property::<u64>("current-level-time"),
+        queue.property::<u64>("max-size-time"),
+    )
+}
+
+#[then(expr = "Queues upstream of {word} fill up")]
+async fn check_upstream_queues_fill(
+    w: &mut World,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let queues = upstream_queues(&w.find_element(&element_name)?);
+    if queues.is_empty() {
+        anyhow::bail!("No queue found upstream of {}", element_name);
+    }
+
+    let start = Instant::now();
+    loop {
+        w.check_no_errors()?;
+        if queues.iter().all(queue_is_full) {
+            return Ok(());
+        }
+
+        if start.elapsed() >= DEFAULT_TIMEOUT {
+            anyhow::bail!(
+                "Queues upstream of {} still not full after {} seconds: {:?}",
+                element_name,
+                DEFAULT_TIMEOUT.as_secs(),
+                queues
+                    .iter()
+                    .filter(|q| !queue_is_full(q))
+                    .map(|q| q.name())
+                    .collect::<Vec<_>>()
+            );
+        }
+
+        task::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Compare the SHA-256 checksum of the next buffer flowing through the named
+/// element with the expected one. The actual checksum is stored in
+/// `extra_data` as `<element>-checksum`, making it easy to capture reference
+/// values for deterministic elements.
+#[then(regex = r"The checksum of the next buffer on (\S+) is '([0-9A-Fa-f]+)'$")]
+async fn check_next_buffer_checksum(
+    w: &mut World,
+    element_name: String,
+    expected: String,
+) -> Result<(), anyhow::Error> {
+    let pad = w.find_data_pad(&element_name)?;
+
+    let probed = probe_buffers(&pad, 1, DEFAULT_TIMEOUT).await?;
+    let buffer = &probed.first().ok_or_else(|| {
+        anyhow::anyhow!(
+            "No buffer flowed through {} after {} seconds",
+            element_name,
+            DEFAULT_TIMEOUT.as_secs()
+        )
+    })?;
+
+    let map = buffer.map_readable()?;
+    let checksum = glib::compute_checksum_for_data(glib::ChecksumType::Sha256, &map)
+        .ok_or_else(|| anyhow::anyhow!("Could not compute buffer checksum"))?;
+    w.extra_data
+        .set(&format!("{}-checksum", element_name), checksum.as_str());
+
+    if !checksum.eq_ignore_ascii_case(&expected) {
+        anyhow::bail!(
+            "Buffer on {} has checksum {} instead of {}",
+            element_name,
+            checksum,
+            expected
+        );
+    }
+
+    Ok(())
+}
+
+#[then(regex = r"The pipeline redirects to '(.*)' within (\S+) (\S+)$")]
+async fn check_redirect(
+    w: &mut World,
+    location: String,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let timeout = parse_duration(value, &unit)?;
+    let msg = w
+        .wait_for_message(timeout, |msg| {
+            matches!(msg.view(), gst::MessageView::Redirect(..))
+        })
+        .await
+        .ok_or_else(|| anyhow::anyhow!("No redirect message posted after {} {}", value, unit))?;
+
+    if let gst::MessageView::Redirect(redirect) = msg.view() {
+        let locations = redirect
+            .entries()
+            .into_iter()
+            .map(|(location, _, _)| location)
+            .collect::<Vec<_>>();
+        if !locations.contains(&location.as_str()) {
+            anyhow::bail!(
+                "Pipeline redirected to {:?} instead of {}",
+                locations,
+                location
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Start counting emissions of the named signal. Only signals without return
+/// value can be counted.
+#[when(regex = r"I start counting signal '(\S+)' on (\S+)$")]
+fn start_counting_signal(
+    w: &mut World,
+    signal_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let key = (element_name.clone(), signal_name.clone());
+    if w.signal_counters.contains_key(&key) {
+        anyhow::bail!(
+            "Already counting signal '{}' on {}",
+            signal_name,
+            element_name
+        );
+    }
+
+    let element = w.find_element(&element_name)?;
+    let signal_id =
+        glib::subclass::SignalId::lookup(&signal_name, element.type_()).ok_or_else(|| {
+            anyhow::anyhow!("Element {} has no signal '{}'", element_name, signal_name)
+        })?;
+    if glib::Type::from(signal_id.query().return_type()) != glib::Type::UNIT {
+        anyhow::bail!(
+            "Signal '{}' on {} has a return value and can't be counted",
+            signal_name,
+            element_name
+        );
+    }
+
+    let count = Arc::new(AtomicUsize::new(0));
+    let handler_id = element.connect(&signal_name, false, {
+        let count = count.clone();
+        move |_| {
+            count.fetch_add(1, Ordering::SeqCst);
+            None
+        }
+    });
+
+    w.signal_counters.insert(
+        key,
+        SignalCounter {
+            element,
+            handler_id,
+            count,
+        },
+    );
+    Ok(())
+}
+
+#[then(regex = r"Signal '(\S+)' on (\S+) fired (\d+) times$")]
+fn check_signal_count(
+    w: &mut World,
+    signal_name: String,
+    element_name: String,
+    expected: usize,
+) -> Result<(), anyhow::Error> {
+    let counter = w
+        .signal_counters
+        .get(&(element_name.clone(), signal_name.clone()))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Signal '{}' on {} is not being counted",
+                signal_name,
+                element_name
+            )
+        })?;
+
+    let count = counter.count.load(Ordering::SeqCst);
+    if count != expected {
+        anyhow::bail!(
+            "Signal '{}' on {} fired {} times instead of {}",
+            signal_name,
+            element_name,
+            count,
+            expected
+        );
+    }
+
+    Ok(())
+}
+
+/// Media types (`video`, `audio`, ...) of the streams exposed by the decodebin
+/// elements of the pipeline.
+fn decodebin_stream_types(w: &World) -> Result<Vec<String>, anyhow::Error> {
+    Ok(w.pipeline_elements()?
+        .iter()
+        .filter(|element| {
+            element.factory().is_some_and(|f| {
+                matches!(
+                    f.name().as_str(),
+                    "decodebin" | "decodebin3" | "uridecodebin" | "uridecodebin3"
+                )
+            })
+        })
+        .flat_map(|decodebin| decodebin.src_pads())
+        .filter_map(|pad| pad.current_caps())
+        .filter_map(|caps| {
+            caps.structure(0)
+                .and_then(|s| s.name().split('/').next().map(String::from))
+        })
+        .collect())
+}
+
+#[then(expr = "decodebin exposes a {word} stream")]
+async fn check_decodebin_stream(w: &mut World, media_type: String) -> Result<(), anyhow::Error> {
+    let start = Instant::now();
+    loop {
+        let stream_types = decodebin_stream_types(w)?;
+        if stream_types.contains(&media_type) {
+            return Ok(());
+        }
+
+        if start.elapsed() >= DEFAULT_TIMEOUT {
+            anyhow::bail!(
+                "No {} stream exposed by decodebin after {} seconds, exposed streams: {:?}",
+                media_type,
+                DEFAULT_TIMEOUT.as_secs(),
+                stream_types
+            );
+        }
+
+        task::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+fn parse_stream_type(stream_type: &str) -> Result<gst::StreamType, anyhow::Error> {
+    Ok(match stream_type.to_lowercase().as_str() {
+        "audio" => gst::StreamType::AUDIO,
+        "video" => gst::StreamType::VIDEO,
+        "text" => gst::StreamType::TEXT,
+        "container" => gst::StreamType::CONTAINER,
+        _ => anyhow::bail!(
+            "Invalid stream type: {} only [audio, video, text, container] are supported",
+            stream_type
+        ),
+    })
+}
+
+/// Select the stream at `index` (starting from 0) amongst the streams of the
+/// given type in the last collection posted on the bus. Streams of other types
+/// previously selected are kept, defaulting to the first stream of each type.
+#[when(expr = "I select the {word} stream numbered {int}")]
+fn select_stream(w: &mut World, stream_type: String, index: usize) -> Result<(), anyhow::Error> {
+    let stream_type = parse_stream_type(&stream_type)?;
+    let collection = match w
+        .last_message(|msg| matches!(msg.view(), gst::MessageView::StreamCollection(..)))
+        .as_ref()
+        .map(|msg| msg.view())
+    {
+        Some(gst::MessageView::StreamCollection(msg)) => msg.stream_collection(),
+        _ => anyhow::bail!("No stream collection posted on the bus yet"),
+    };
+
+    let streams = collection.iter().collect::<Vec<_>>();
+    let selected = streams
+        .iter()
+        .filter(|stream| stream.stream_type() == stream_type)
+        .nth(index)
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Stream collection has no {:?} stream numbered {}",
+                stream_type,
+                index
+            )
+        })?;
+
+    let previously_selected = match w
+        .last_message(|msg| matches!(msg.view(), gst::MessageView::StreamsSelected(..)))
+        .as_ref()
+        .map(|msg| msg.view())
+    {
+        Some(gst::MessageView::StreamsSelected(msg)) => msg.streams(),
+        _ => Vec::new(),
+    };
+
+    let mut selection = vec![selected.clone()];
+    for stream in streams.iter() {
+        let other_type = stream.stream_type();
+        if other_type == stream_type || selection.iter().any(|s| s.stream_type() == other_type) {
+            continue;
+        }
+
+        selection.push(
+            previously_selected
+                .iter()
+                .find(|s| s.stream_type() == other_type)
+                .unwrap_or(stream)
+                .clone(),
+        );
+    }
+
+    let stream_ids = selection
+        .iter()
+        .filter_map(|stream| stream.stream_id())
+        .collect::<Vec<_>>();
+    let stream_ids = stream_ids.iter().map(|id| id.as_str()).collect::<Vec<_>>();
+
+    gst::debug!(CAT, "Selecting streams {:?}", stream_ids);
+    if !w
+        .get_pipeline()?
+        .send_event(gst::event::SelectStreams::new(&stream_ids))
+    {
+        anyhow::bail!("Pipeline refused the stream selection {:?}", stream_ids);
+    }
+
+    Ok(())
+}
+
+/// Index amongst the streams of type `stream_type` of the selected stream of
+/// that type, as notified by the last `StreamsSelected` message.
+fn active_stream_index(w: &World, stream_type: gst::StreamType) -> Option<usize> {
+    let msg = w.last_message(|msg| matches!(msg.view(), gst::MessageView::StreamsSelected(..)))?;
+    let selected = match msg.view() {
+        gst::MessageView::StreamsSelected(selected) => selected,
+        _ => unreachable!(),
+    };
+
+    let active_id = selected
+        .streams()
+        .iter()
+        .find(|stream| stream.stream_type() == stream_type)
+        .and_then(|stream| stream.stream_id())?;
+
+    selected
+        .stream_collection()
+        .iter()
+        .filter(|stream| stream.stream_type() == stream_type)
+        .position(|stream| stream.stream_id().as_ref() == Some(&active_id))
+}
+
+#[then(expr = "The active {word} stream is numbered {int}")]
+async fn check_active_stream(
+    w: &mut World,
+    stream_type_name: String,
+    expected: usize,
+) -> Result<(), anyhow::Error> {
+    let stream_type = parse_stream_type(&stream_type_name)?;
+
+    let start = Instant::now();
+    loop {
+        let active = active_stream_index(w, stream_type);
+        if active == Some(expected) {
+            return Ok(());
+        }
+
+        if start.elapsed() >= DEFAULT_TIMEOUT {
+            match active {
+                Some(active) => anyhow::bail!(
+                    "Active {} stream is numbered {} instead of {}",
+                    stream_type_name,
+                    active,
+                    expected
+                ),
+                None => anyhow::bail!(
+                    "No {} stream selected after {} seconds",
+                    stream_type_name,
+                    DEFAULT_TIMEOUT.as_secs()
+                ),
+            }
+        }
+
+        task::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Check the largest interval between consecutive buffers on the named
+/// element, probed until EOS or for 5 seconds at most.
+#[then(expr = "No frame gap on {word} exceeds {word} {word}")]
+async fn check_frame_gaps(
+    w: &mut World,
+    element_name: String,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let max_gap = parse_duration(value, &unit)?;
+    let pad = w.find_data_pad(&element_name)?;
+
+    let arrivals = probe_pad(&pad, usize::MAX, DEFAULT_TIMEOUT, |_, arrival| arrival).await?;
+    let (gap, at) = arrivals
+        .windows(2)
+        .map(|pair| {
+            (
+                pair[1].saturating_duration_since(pair[0]),
+                pair[0].saturating_duration_since(arrivals[0]),
+            )
+        })
+        .max()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Not enough buffers on {} to measure frame gaps: {}",
+                element_name,
+                arrivals.len()
+            )
+        })?;
+
+    if gap > max_gap {
+        anyhow::bail!(
+            "Largest frame gap on {} is {:?}, {:?} after the first frame",
+            element_name,
+            gap,
+            at
+        );
+    }
+
+    Ok(())
+}
+
+/// Check the peak number of streaming threads running concurrently. Threads are
+/// accounted for from the `enter`/`leave` stream-status messages posted on the
+/// bus, so only the streaming tasks managed by GStreamer (sources, queues,
+/// demuxers, ...) are taken into account, not the threads elements or
+/// libraries may create on their own.
+#[then(expr = "The pipeline spawns at most {int} streaming threads")]
+fn check_streaming_threads(w: &mut World, max_threads: usize) -> Result<(), anyhow::Error> {
+    let mut running = 0usize;
+    let mut peak = 0usize;
+    for msg in w.messages.lock().unwrap().iter() {
+        if let gst::MessageView::StreamStatus(status) = msg.view() {
+            match status.get().0 {
+                gst::StreamStatusType::Enter => {
+                    running += 1;
+                    peak = peak.max(running);
+                }
+                gst::StreamStatusType::Leave => running = running.saturating_sub(1),
+                _ => (),
+            }
+        }
+    }
+
+    if peak > max_threads {
+        anyhow::bail!(
+            "Pipeline spawned {} streaming threads, more than {}",
+            peak,
+            max_threads
+        );
+    }
+
+    Ok(())
+}
+
+/// State transitions of the named element, as notified on the bus.
+fn state_transitions(w: &World, element_name: &str) -> Vec<(gst::State, gst::State)> {
+    w.messages
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|msg| match msg.view() {
+            gst::MessageView::StateChanged(changed)
+                if changed.src().is_some_and(|src| src.name() == element_name) =>
+            {
+                Some((changed.old(), changed.current()))
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+#[then(expr = "{word} transitioned through PAUSED to PLAYING")]
+async fn check_paused_to_playing(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    w.find_element(&element_name)?;
+
+    let start = Instant::now();
+    loop {
+        let transitions = state_transitions(w, &element_name);
+        if let Some(paused) = transitions
+            .iter()
+            .position(|(_, current)| *current == gst::State::Paused)
+        {
+            if transitions[paused..]
+                .iter()
+                .any(|(_, current)| *current == gst::State::Playing)
+            {
+                return Ok(());
+            }
+        }
+
+        if start.elapsed() >= DEFAULT_TIMEOUT {
+            anyhow::bail!(
+                "{} did not transition through PAUSED to PLAYING, transitions: {:?}",
+                element_name,
+                transitions
+            );
+        }
+
+        task::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Parse a `<width>x<height>` resolution.
+fn parse_resolution(resolution: &str) -> Result<(i32, i32), anyhow::Error> {
+    resolution
+        .split_once('x')
+        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+        .ok_or_else(|| anyhow::anyhow!("Invalid resolution: {}, expected WIDTHxHEIGHT", resolution))
+}
+
+/// Change the resolution set on the named capsfilter while the pipeline is
+/// running, and check the new resolution got negotiated downstream of it and
+/// frames are still flowing afterwards.
+#[then(expr = "{word} handles a resolution change to {word}")]
+async fn check_resolution_change(
+    w: &mut World,
+    capsfilter_name: String,
+    resolution: String,
+) -> Result<(), anyhow::Error> {
+    let (width, height) = parse_resolution(&resolution)?;
+    let capsfilter = w.find_element(&capsfilter_name)?;
+    if capsfilter.find_property("caps").is_none() {
+        anyhow::bail!("Element {} has no caps property", capsfilter_name);
+    }
+
+    let mut caps = capsfilter.property::<gst::Caps>("caps");
+    if caps.is_any() || caps.is_empty() {
+        caps = gst::Caps::new_empty_simple("video/x-raw");
+    }
+    for structure in caps.make_mut().iter_mut() {
+        structure.set("width", width);
+        structure.set("height", height);
+    }
+    gst::debug!(CAT, "Changing caps on {} to {}", capsfilter_name, caps);
+    capsfilter.set_property("caps", &caps);
+
+    let srcpad = w.find_pad("src", &capsfilter_name)?;
+    let negotiated = |pad: &gst::Pad| {
+        pad.current_caps()
+            .and_then(|caps| {
+                caps.structure(0).map(|s| {
+                    s.get::<i32>("width").ok() == Some(width)
+                        && s.get::<i32>("height").ok() == Some(height)
+                })
+            })
+            .unwrap_or(false)
+    };
+
+    let start = Instant::now();
+    while !negotiated(&srcpad) {
+        w.check_no_errors()?;
+        if start.elapsed() >= DEFAULT_TIMEOUT {
+            anyhow::bail!(
+                "{}x{} not negotiated after {} seconds, current caps: {:?}",
+                width,
+                height,
+                DEFAULT_TIMEOUT.as_secs(),
+                srcpad.current_caps()
+            );
+        }
+
+        task::sleep(Duration::from_millis(100)).await;
+    }
+
+    if probe_buffers(&srcpad, 1, DEFAULT_TIMEOUT).await?.is_empty() {
+        anyhow::bail!(
+            "No frame flowed through {} after the resolution change",
+            capsfilter_name
+        );
+    }
+
+    w.check_no_errors()
+}
+
+/// Check no GLib critical or warning got logged during the scenario. Messages
+/// are only recorded in the scenarios using this step, from their start.
+#[then(expr = "No GLib criticals are logged")]
+fn check_no_glib_criticals(w: &mut World) -> Result<(), anyhow::Error> {
+    let glib_warnings = w.glib_warnings.lock().unwrap();
+    if !glib_warnings.is_empty() {
+        anyhow::bail!("GLib logged:\n{}", glib_warnings.join("\n"));
+    }
+
+    Ok(())
+}
+
+/// Wait up to `timeout` for `count` buffers to flow through `pad`, returning
+/// the number of buffers seen.
+async fn count_buffers(
+    pad: &gst::Pad,
+    count: usize,
+    timeout: Duration,
+) -> Result<usize, anyhow::Error> {
+    let (sender, mut receiver) = mpsc::unbounded();
+    let probe_id = pad
+        .add_probe(
+            gst::PadProbeType::BUFFER | gst::PadProbeType::BUFFER_LIST,
+            move |_, info| {
+                let n_buffers = match &info.data {
+                    Some(gst::PadProbeData::BufferList(list)) => list.len(),
+                    _ => 1,
+                };
+                let _ = sender.unbounded_send(n_buffers);
+
+                gst::PadProbeReturn::Ok
+            },
+        )
+        .ok_or_else(|| anyhow::anyhow!("Could not add a buffer probe on {}", pad.name()))?;
+
+    let deadline = Instant::now() + timeout;
+    let mut seen = 0;
+    while seen < count {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match future::timeout(remaining, receiver.next()).await {
+            Ok(Some(n_buffers)) => seen += n_buffers,
+            _ => break,
+        }
+    }
+    pad.remove_probe(probe_id);
+
+    Ok(seen)
+}
+
+/// The pad data flows through on `element`: its `sink` pad, or its `src` pad
+/// for source elements.
+fn data_pad(element: &gst::Element) -> Result<gst::Pad, anyhow::Error> {
+    element
+        .static_pad("sink")
+        .or_else(|| element.static_pad("src"))
+        .ok_or_else(|| anyhow::anyhow!("Element {} has no sink or src pad", element.name()))
+}
+
+/// Play the pipeline until the given number of buffers went through the named
+/// element, then stop it cleanly. This bounds live pipelines which would
+/// otherwise never reach EOS, e.g. `When I run pipeline until 10 buffers pass sink`.
+/// `pipeline` designates all the pipelines, any other name only the named
+/// pipeline, see `World::set_named_pipeline()`.
+#[when(expr = "I run {word} until {int} buffers pass {word}")]
+async fn run_until_buffers(
+    w: &mut World,
+    pipeline: String,
+    count: usize,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let named_pipeline = match pipeline.as_str() {
+        "pipeline" => None,
+        name => Some(w.get_named_pipeline(name)?.clone()),
+    };
+
+    let pad = match &named_pipeline {
+        Some(named_pipeline) => data_pad(
+            &named_pipeline
+                .downcast_ref::<gst::Bin>()
+                .unwrap()
+                .by_name_recurse_up(&element_name)
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Could not find element {} in {}", element_name, pipeline)
+                })?,
+        )?,
+        None => w.find_data_pad(&element_name)?,
+    };
+    match &named_pipeline {
+        Some(named_pipeline) => w.change_state(named_pipeline, gst::State::Playing).await?,
+        None => w.set_pipeline_state("play".to_string()).await?,
+    }
+
+    let seen = count_buffers(&pad, count, DEFAULT_TIMEOUT).await?;
+    if seen < count {
+        anyhow::bail!(
+            "Only {} buffers out of {} went through {} after {} seconds",
+            seen,
+            count,
+            element_name,
+            DEFAULT_TIMEOUT.as_secs()
+        );
+    }
+
+    match &named_pipeline {
+        Some(named_pipeline) => w.change_state(named_pipeline, gst::State::Null).await,
+        None => w.set_pipeline_state("stop".to_string()).await,
+    }
+}
+
+/// Minimum latency reported by a latency query run with `run_query`.
+fn query_min_latency<F>(run_query: F) -> Option<gst::ClockTime>
+where
+    F: FnOnce(&mut gst::query::Latency) -> bool,
+{
+    let mut query = gst::query::Latency::new();
+    if run_query(&mut query) {
+        Some(query.result().1)
+    } else {
+        None
+    }
+}
+
+/// Compare the `latency` property of the named element with the latency it
+/// adds, computed from latency queries run upstream of it and on its src pad.
+/// `guint` properties are expected to be in milliseconds (e.g. on
+/// `rtpjitterbuffer`), 64-bit ones in nanoseconds.
+#[then(expr = "The configured latency on {word} matches the queried latency")]
+fn check_configured_latency(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    let element = w.find_element(&element_name)?;
+    let pspec = element
+        .find_property("latency")
+        .ok_or_else(|| anyhow::anyhow!("Element {} has no latency property", element_name))?;
+
+    let configured = match pspec.value_type() {
+        glib::Type::U32 => gst::ClockTime::from_mseconds(element.property::<u32>("latency").into()),
+        glib::Type::I32 => {
+            gst::ClockTime::from_mseconds(element.property::<i32>("latency").max(0) as u64)
+        }
+        glib::Type::U64 => gst::ClockTime::from_nseconds(element.property::<u64>("latency")),
+        glib::Type::I64 => {
+            gst::ClockTime::from_nseconds(element.property::<i64>("latency").max(0) as u64)
+        }
+        type_ => anyhow::bail!("Unsupported latency property type: {}", type_),
+    };
+
+    let srcpad = w.find_pad("src", &element_name)?;
+    let total = query_min_latency(|query| srcpad.query(query))
+        .ok_or_else(|| anyhow::anyhow!("Latency query on {}:src not answered", element_name))?;
+    let upstream = match element.static_pad("sink") {
+        Some(sinkpad) => query_min_latency(|query| sinkpad.peer_query(query)).ok_or_else(|| {
+            anyhow::anyhow!("Latency query upstream of {} not answered", element_name)
+        })?,
+        None => gst::ClockTime::ZERO,
+    };
+
+    let queried = total.saturating_sub(upstream);
+    if clock_time_diff(configured, queried) > TIMESTAMP_TOLERANCE {
+        anyhow::bail!(
+            "{} is configured with a latency of {} but adds {} of latency",
+            element_name,
+            configured,
+            queried
+        );
+    }
+
+    Ok(())
+}
+
+/// Pull a sample from the named `appsink`, waiting at most `timeout`.
+fn try_pull_sample(
+    w: &World,
+    element_name: &str,
+    timeout: gst::ClockTime,
+) -> Result<Option<gst::Sample>, anyhow::Error> {
+    let appsink = w.find_element(element_name)?;
+    if appsink.factory().is_none_or(|f| f.name() != "appsink") {
+        anyhow::bail!("Element {} is not an appsink", element_name);
+    }
+
+    Ok(appsink.emit_by_name::<Option<gst::Sample>>("try-pull-sample", &[&timeout.nseconds()]))
+}
+
+#[then(expr = "The subtitle on {word} contains '{word}'")]
+async fn check_subtitle(
+    w: &mut World,
+    element_name: String,
+    expected: String,
+) -> Result<(), anyhow::Error> {
+    let mut seen = Vec::new();
+
+    let start = Instant::now();
+    while start.elapsed() < DEFAULT_TIMEOUT {
+        let sample = match try_pull_sample(w, &element_name, gst::ClockTime::ZERO)? {
+            Some(sample) => sample,
+            None => {
+                task::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+
+        if let Some(buffer) = sample.buffer() {
+            let map = buffer.map_readable()?;
+            let text = String::from_utf8_lossy(&map).into_owned();
+            if text.contains(&expected) {
+                return Ok(());
+            }
+            seen.push(text);
+        }
+    }
+
+    anyhow::bail!(
+        "No subtitle containing '{}' on {} after {} seconds, seen: {:?}",
+        expected,
+        element_name,
+        DEFAULT_TIMEOUT.as_secs(),
+        seen
+    )
+}
+
+#[then(expr = "Closed captions are present on the {word} pad of {word}")]
+async fn check_closed_captions(
+    w: &mut World,
+    pad_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let pad = w.find_pad(&pad_name, &element_name)?;
+
+    let has_captions = probe_pad(&pad, PROBED_BUFFERS_COUNT, DEFAULT_TIMEOUT, |buffer, _| {
+        buffer.meta::<gstvideo::VideoCaptionMeta>().is_some()
+    })
+    .await?;
+    if !has_captions.iter().any(|has_captions| *has_captions) {
+        anyhow::bail!(
+            "None of the {} buffers seen on {}:{} carried closed captions",
+            has_captions.len(),
+            element_name,
+            pad_name
+        );
+    }
+
+    Ok(())
+}
+
+/// First structure of the caps currently negotiated on the named pad.
+fn negotiated_caps_structure(
+    w: &World,
+    pad_name: &str,
+    element_name: &str,
+) -> Result<gst::Structure, anyhow::Error> {
+    w.find_pad(pad_name, element_name)?
+        .current_caps()
+        .and_then(|caps| caps.structure(0).map(|s| s.to_owned()))
+        .ok_or_else(|| anyhow::anyhow!("No caps negotiated on {}:{} yet", element_name, pad_name))
+}
+
+#[then(expr = "The colorimetry on the {word} pad of {word} is {word}")]
+fn check_colorimetry(
+    w: &mut World,
+    pad_name: String,
+    element_name: String,
+    expected: String,
+) -> Result<(), anyhow::Error> {
+    let structure = negotiated_caps_structure(w, &pad_name, &element_name)?;
+
+    let colorimetry = structure.get::<String>("colorimetry").ok();
+    if colorimetry.as_deref() != Some(expected.as_str()) {
+        anyhow::bail!(
+            "Colorimetry on {}:{} is {:?} instead of {}",
+            element_name,
+            pad_name,
+            colorimetry,
+            expected
+        );
+    }
+
+    Ok(())
+}
+
+/// Check the HDR metadata, `mastering-display-info` and `content-light-level`
+/// caps fields, survived up to the named pad.
+#[then(expr = "HDR metadata is present on the {word} pad of {word}")]
+fn check_hdr_metadata(
+    w: &mut World,
+    pad_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let structure = negotiated_caps_structure(w, &pad_name, &element_name)?;
+
+    let missing = ["mastering-display-info", "content-light-level"]
+        .into_iter()
+        .filter(|field| !structure.has_field(field))
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Caps on {}:{} lack {:?}: {}",
+            element_name,
+            pad_name,
+            missing,
+            structure
+        );
+    }
+
+    Ok(())
+}
+
+/// Check whether the video flowing through the named element is `progressive`
+/// or `interlaced`, from the `interlace-mode` caps field. Any mode other than
+/// `progressive` (`interleaved`, `mixed`, `fields`, ...) counts as interlaced.
+#[then(expr = "The video on {word} is {word}")]
+fn check_interlacing(
+    w: &mut World,
+    element_name: String,
+    mode: String,
+) -> Result<(), anyhow::Error> {
+    let expect_progressive = match mode.as_str() {
+        "progressive" => true,
+        "interlaced" => false,
+        _ => anyhow::bail!(
+            "Invalid interlacing mode: {} only [progressive, interlaced] are supported",
+            mode
+        ),
+    };
+
+    let pad = w.find_data_pad(&element_name)?;
+    let caps = pad
+        .current_caps()
+        .ok_or_else(|| anyhow::anyhow!("No caps negotiated on {} yet", element_name))?;
+    let structure = caps
+        .structure(0)
+        .ok_or_else(|| anyhow::anyhow!("Empty caps negotiated on {}", element_name))?;
+
+    // Caps without `interlace-mode` field are progressive.
+    let actual = structure
+        .get::<String>("interlace-mode")
+        .unwrap_or_else(|_| "progressive".to_string());
+    if (actual == "progressive") != expect_progressive {
+        anyhow::bail!(
+            "Video on {} is {} instead of {}",
+            element_name,
+            actual,
+            mode
+        );
+    }
+
+    Ok(())
+}
+
+/// Keep the last `count` samples flowing through the named element, so that
+/// following steps can check properties over that window. Samples hold a
+/// reference on their buffer, preventing it from being recycled by buffer
+/// pools, so large windows of raw video can use a lot of memory. The recording
+/// probe is removed when the scenario ends.
+#[given(expr = "I record the last {int} samples on {word}")]
+fn record_samples(w: &mut World, count: usize, element_name: String) -> Result<(), anyhow::Error> {
+    if count == 0 {
+        anyhow::bail!("Invalid number of samples to record: {}", count);
+    }
+    if w.recorded_samples.contains_key(&element_name) {
+        anyhow::bail!("Already recording samples on {}", element_name);
+    }
+
+    let pad = w.find_data_pad(&element_name)?;
+    let samples = Arc::new(Mutex::new(VecDeque::with_capacity(count)));
+    let probe_id = pad
+        .add_probe(
+            gst::PadProbeType::BUFFER | gst::PadProbeType::BUFFER_LIST,
+            {
+                let samples = samples.clone();
+                move |pad, info| {
+                    let buffers = match &info.data {
+                        Some(gst::PadProbeData::Buffer(buffer)) => vec![buffer.clone()],
+                        Some(gst::PadProbeData::BufferList(list)) => list.iter_owned().collect(),
+                        _ => Vec::new(),
+                    };
+
+                    let caps = pad.current_caps();
+                    let mut samples = samples.lock().unwrap();
+                    for buffer in buffers {
+                        if samples.len() == count {
+                            samples.pop_front();
+                        }
+                        let mut builder = gst::Sample::builder().buffer(&buffer);
+                        if let Some(caps) = caps.as_ref() {
+                            builder = builder.caps(caps);
+                        }
+                        samples.push_back(builder.build());
+                    }
+
+                    gst::PadProbeReturn::Ok
+                }
+            },
+        )
+        .ok_or_else(|| anyhow::anyhow!("Could not add a recording probe on {}", element_name))?;
+
+    w.probes.push((pad, probe_id));
+    w.recorded_samples.insert(element_name, samples);
+    Ok(())
+}
+
+/// Samples recorded so far on the named element, oldest first.
+fn recorded_samples(w: &World, element_name: &str) -> Result<Vec<gst::Sample>, anyhow::Error> {
+    let samples = w
+        .recorded_samples
+        .get(element_name)
+        .ok_or_else(|| anyhow::anyhow!("Samples on {} are not being recorded", element_name))?
+        .lock()
+        .unwrap()
+        .iter()
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if samples.is_empty() {
+        anyhow::bail!("No sample recorded on {} yet", element_name);
+    }
+
+    Ok(samples)
+}
+
+#[then(expr = "The recorded samples on {word} have the same caps")]
+fn check_recorded_samples_caps(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    let samples = recorded_samples(w, &element_name)?;
+
+    let first = samples[0].caps();
+    if let Some((i, sample)) = samples
+        .iter()
+        .enumerate()
+        .find(|(_, sample)| sample.caps() != first)
+    {
+        anyhow::bail!(
+            "Recorded sample #{} on {} has caps {:?} instead of {:?}",
+            i,
+            element_name,
+            sample.caps(),
+            first
+        );
+    }
+
+    Ok(())
+}
+
+#[then(expr = "The recorded samples on {word} have increasing timestamps")]
+fn check_recorded_samples_timestamps(
+    w: &mut World,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let samples = recorded_samples(w, &element_name)?;
+
+    let timestamps = samples
+        .iter()
+        .map(|sample| sample.buffer().and_then(|buffer| buffer.pts()))
+        .collect::<Vec<_>>();
+    for (i, pair) in timestamps.windows(2).enumerate() {
+        if let (Some(previous), Some(current)) = (pair[0], pair[1]) {
+            if current < previous {
+                anyhow::bail!(
+                    "Recorded sample #{} on {} has PTS {} before the previous one {}",
+                    i + 1,
+                    element_name,
+                    current,
+                    previous
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Decode the image at `path` into a video sample.
+fn load_reference_frame(path: &Path) -> Result<gst::Sample, anyhow::Error> {
+    let pipeline =
+        gst::parse_launch("filesrc name=src ! decodebin ! videoconvert ! appsink name=sink")?
+            .downcast::<gst::Bin>()
+            .unwrap();
+    pipeline
+        .by_name("src")
+        .unwrap()
+        .set_property("location", path.to_str());
+    let appsink = pipeline.by_name("sink").unwrap();
+
+    pipeline
+        .set_state(gst::State::Paused)
+        .map_err(|_| anyhow::anyhow!("Could not decode reference frame {}", path.display()))?;
+    let (res, _, _) = pipeline.state(gst::ClockTime::from_nseconds(
+        DEFAULT_TIMEOUT.as_nanos() as u64
+    ));
+    let sample = res
+        .ok()
+        .and_then(|_| appsink.emit_by_name::<Option<gst::Sample>>("pull-preroll", &[]));
+    let _ = pipeline.set_state(gst::State::Null);
+
+    sample.ok_or_else(|| anyhow::anyhow!("Could not decode reference frame {}", path.display()))
+}
+
+/// Convert the frame held by `sample` to RGB.
+fn rgb_frame(
+    sample: &gst::Sample,
+) -> Result<gstvideo::VideoFrame<gstvideo::video_frame::Readable>, anyhow::Error> {
+    let caps = gst::Caps::builder("video/x-raw")
+        .field("format", "RGB")
+        .build();
+    let converted = gstvideo::convert_sample(
+        sample,
+        &caps,
+        gst::ClockTime::from_nseconds(DEFAULT_TIMEOUT.as_nanos() as u64),
+    )?;
+
+    let info = gstvideo::VideoInfo::from_caps(
+        converted
+            .caps()
+            .ok_or_else(|| anyhow::anyhow!("Converted frame has no caps"))?,
+    )?;
+    let buffer = converted
+        .buffer_owned()
+        .ok_or_else(|| anyhow::anyhow!("Converted frame has no buffer"))?;
+    gstvideo::VideoFrame::from_buffer_readable(buffer, &info)
+        .map_err(|_| anyhow::anyhow!("Could not map converted frame"))
+}
+
+/// Peak signal-to-noise ratio, in dB, between the frames of two samples.
+/// Identical frames have an infinite PSNR.
+fn frame_psnr(sample: &gst::Sample, reference: &gst::Sample) -> Result<f64, anyhow::Error> {
+    let frame = rgb_frame(sample)?;
+    let reference = rgb_frame(reference)?;
+
+    if (frame.width(), frame.height()) != (reference.width(), reference.height()) {
+        anyhow::bail!(
+            "Frame is {}x{} while reference is {}x{}",
+            frame.width(),
+            frame.height(),
+            reference.width(),
+            reference.height()
+        );
+    }
+
+    fn rows(
+        frame: &gstvideo::VideoFrame<gstvideo::video_frame::Readable>,
+        row_size: usize,
+    ) -> Vec<&[u8]> {
+        let stride = frame.plane_stride()[0] as usize;
+        let data = frame.plane_data(0).unwrap();
+        (0..frame.height() as usize)
+            .map(|row| &data[row * stride..row * stride + row_size])
+            .collect()
+    }
+
+    let row_size = frame.width() as usize * 3;
+    let squared_error = rows(&frame, row_size)
+        .iter()
+        .zip(rows(&reference, row_size).iter())
+        .flat_map(|(a, b)| a.iter().zip(b.iter()))
+        .map(|(a, b)| (f64::from(*a) - f64::from(*b)).powi(2))
+        .sum::<f64>();
+    let mse = squared_error / (row_size * frame.height() as usize) as f64;
+
+    Ok(10. * (255f64.powi(2) / mse).log10())
+}
+
+/// Compare each sample recorded on the named element with the reference image
+/// of the same index in the given directory, named `frame_000.png`,
+/// `frame_001.png`, ... Relative directories are resolved from the directory
+/// of the feature file. A frame matches its reference when their PSNR is at
+/// least 35 dB.
+#[then(regex = r"The recorded samples on (\S+) match references in '(.*)'$")]
+fn check_recorded_samples_references(
+    w: &mut World,
+    element_name: String,
+    directory: String,
+) -> Result<(), anyhow::Error> {
+    let samples = recorded_samples(w, &element_name)?;
+    let directory = w.resolve_path(&directory);
+
+    for (i, sample) in samples.iter().enumerate() {
+        let path = directory.join(format!("frame_{:03}.png", i));
+        let psnr = frame_psnr(sample, &load_reference_frame(&path)?).map_err(|err| {
+            anyhow::anyhow!("Recorded sample #{} on {}: {}", i, element_name, err)
+        })?;
+
+        gst::debug!(CAT, "PSNR of sample #{} on {}: {}", i, element_name, psnr);
+        if psnr < MIN_PSNR {
+            anyhow::bail!(
+                "Recorded sample #{} on {} doesn't match {}, PSNR is {:.2} dB",
+                i,
+                element_name,
+                path.display(),
+                psnr
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[then(expr = "The negotiated caps on the {word} pad of {word} fit its template")]
+fn check_caps_fit_template(
+    w: &mut World,
+    pad_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let pad = w.find_pad(&pad_name, &element_name)?;
+    let caps = pad.current_caps().ok_or_else(|| {
+        anyhow::anyhow!("No caps negotiated on {}:{} yet", element_name, pad_name)
+    })?;
+
+    let template_caps = pad.pad_template_caps();
+    if !caps.is_subset(&template_caps) {
+        anyhow::bail!(
+            "Caps negotiated on {}:{} don't fit its template: {} not a subset of {}",
+            element_name,
+            pad_name,
+            caps,
+            template_caps
+        );
+    }
+
+    Ok(())
+}
+
+/// Set a property while the pipeline is playing and check a caps event
+/// carrying different caps than the ones negotiated before reaches the named
+/// pad, e.g. `Then Setting src::pattern to ball renegotiates the src pad of capsfilter`.
+#[then(expr = "Setting {word} to {word} renegotiates the {word} pad of {word}")]
+async fn check_property_renegotiates(
+    w: &mut World,
+    propname: String,
+    value: String,
+    pad_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let (_, current, _) = w.get_pipeline()?.state(gst::ClockTime::ZERO);
+    if current != gst::State::Playing {
+        anyhow::bail!("Pipeline is {:?}, not PLAYING", current);
+    }
+
+    let pad = w.find_pad(&pad_name, &element_name)?;
+    let previous_caps = pad.current_caps();
+
+    let (sender, mut receiver) = mpsc::unbounded();
+    let probe_id = pad
+        .add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, move |_, info| {
+            if let Some(gst::PadProbeData::Event(event)) = &info.data {
+                if let gst::EventView::Caps(caps) = event.view() {
+                    let _ = sender.unbounded_send(caps.caps_owned());
+                }
+            }
+
+            gst::PadProbeReturn::Ok
+        })
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Could not add an event probe on {}:{}",
+                element_name,
+                pad_name
+            )
+        })?;
+
+    set_property(w, propname.clone(), value.clone())?;
+
+    let deadline = Instant::now() + DEFAULT_TIMEOUT;
+    let mut renegotiated = None;
+    while renegotiated.is_none() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match future::timeout(remaining, receiver.next()).await {
+            Ok(Some(caps)) if Some(&caps) != previous_caps.as_ref() => renegotiated = Some(caps),
+            Ok(Some(_)) => (),
+            _ => break,
+        }
+    }
+    pad.remove_probe(probe_id);
+
+    match renegotiated {
+        Some(caps) => {
+            gst::debug!(
+                CAT,
+                "{}:{} renegotiated to {}",
+                element_name,
+                pad_name,
+                caps
+            );
+            w.check_no_errors()
+        }
+        None => anyhow::bail!(
+            "Setting {} to {} didn't renegotiate {}:{} within {} seconds",
+            propname,
+            value,
+            element_name,
+            pad_name,
+            DEFAULT_TIMEOUT.as_secs()
+        ),
+    }
+}
+
+/// Numeric value held by `value`, if any.
+fn value_as_f64(value: &glib::Value) -> Option<f64> {
+    match value.type_() {
+        glib::Type::I32 => value.get::<i32>().ok().map(f64::from),
+        glib::Type::U32 => value.get::<u32>().ok().map(f64::from),
+        glib::Type::I64 => value.get::<i64>().ok().map(|v| v as f64),
+        glib::Type::U64 => value.get::<u64>().ok().map(|v| v as f64),
+        glib::Type::F32 => value.get::<f32>().ok().map(f64::from),
+        glib::Type::F64 => value.get::<f64>().ok(),
+        _ => None,
+    }
+}
+
+/// Start sampling the numeric property `propname` every `interval`. Samples
+/// are indexed by the property specifier.
+fn start_sampling(w: &mut World, propname: &str, interval: Duration) -> Result<(), anyhow::Error> {
+    if w.samplers.contains_key(propname) {
+        anyhow::bail!("Already sampling {}", propname);
+    }
+
+    let (pspec, obj) = w.find_element_property(propname)?;
+    if value_as_f64(&obj.property_value(pspec.name())).is_none() {
+        anyhow::bail!(
+            "Property {} is of type {}, not numeric",
+            propname,
+            pspec.value_type()
+        );
+    }
+
+    // Sampling happens from another thread, only GstObjects are thread-safe.
+    let obj = obj.downcast::<gst::Object>().map_err(|obj| {
+        anyhow::anyhow!(
+            "Property {} belongs to a {}, not to a GstObject",
+            propname,
+            obj.type_()
+        )
+    })?;
+    let sampler = Sampler::start(interval, move || {
+        value_as_f64(&obj.property_value(pspec.name())).unwrap()
+    });
+    w.samplers.insert(propname.to_string(), sampler);
+    Ok(())
+}
+
+/// Values sampled so far for the property `propname`.
+fn sampled_values(w: &World, propname: &str) -> Result<Vec<(Duration, f64)>, anyhow::Error> {
+    let samples = w
+        .samplers
+        .get(propname)
+        .ok_or_else(|| anyhow::anyhow!("Property {} is not being sampled", propname))?
+        .samples
+        .lock()
+        .unwrap()
+        .clone();
+
+    if samples.is_empty() {
+        anyhow::bail!("Property {} not sampled yet", propname);
+    }
+
+    Ok(samples)
+}
+
+/// Periodically sample a numeric property until the scenario ends, so that
+/// statistics can be checked over its values, e.g.
+/// `Given I sample property queue::current-level-buffers every 100 ms`.
+#[given(expr = "I sample property {word} every {word} {word}")]
+fn sample_property(
+    w: &mut World,
+    propname: String,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let interval = parse_duration(value, &unit)?;
+    if interval.is_zero() {
+        anyhow::bail!("Invalid sampling interval: {} {}", value, unit);
+    }
+
+    start_sampling(w, &propname, interval)
+}
+
+#[then(expr = "The average of {word} is below {word}")]
+fn check_sampled_average(w: &mut World, propname: String, max: f64) -> Result<(), anyhow::Error> {
+    let samples = sampled_values(w, &propname)?;
+
+    let average = samples.iter().map(|(_, v)| v).sum::<f64>() / samples.len() as f64;
+    if average >= max {
+        anyhow::bail!(
+            "Average of {} over {} samples is {}, not below {}",
+            propname,
+            samples.len(),
+            average,
+            max
+        );
+    }
+
+    Ok(())
+}
+
+/// Start sampling the `current-level-bytes` property of the named queue, so
+/// that its peak can be checked at the end of the scenario.
+#[given(expr = "I monitor queued bytes on {word}")]
+fn monitor_queued_bytes(w: &mut World, queue_name: String) -> Result<(), anyhow::Error> {
+    start_sampling(
+        w,
+        &format!("{}::current-level-bytes", queue_name),
+        QUEUE_SAMPLING_INTERVAL,
+    )
+}
+
+#[then(expr = "Peak queued bytes on {word} stayed below {word} {word}")]
+fn check_peak_queued_bytes(
+    w: &mut World,
+    queue_name: String,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let max_bytes = parse_size(value, &unit)?;
+    let samples = sampled_values(w, &format!("{}::current-level-bytes", queue_name))?;
+
+    let (at, peak) = samples
+        .into_iter()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .unwrap();
+
+    if peak >= max_bytes as f64 {
+        anyhow::bail!(
+            "Peak queued bytes on {} reached {} bytes, {:?} after monitoring started",
+            queue_name,
+            peak,
+            at
+        );
+    }
+
+    Ok(())
+}
+
+/// Send a gap event of the given duration on a pad, starting at the current
+/// pipeline position.
+#[when(expr = "I inject a gap of {word} {word} on the {word} pad of {word}")]
+fn inject_gap(
+    w: &mut World,
+    value: u64,
+    unit: String,
+    pad_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let duration = gst::ClockTime::from_nseconds(parse_duration(value, &unit)?.as_nanos() as u64);
+    let position = w
+        .get_pipeline()?
+        .query_position::<gst::ClockTime>()
+        .unwrap_or(gst::ClockTime::ZERO);
+
+    w.send_pad_event(
+        &pad_name,
+        &element_name,
+        gst::event::Gap::builder(position)
+            .duration(duration)
+            .build(),
+    )
+}
+
+#[then(expr = "Data keeps flowing through {word} after the gap")]
+async fn check_flow_after_gap(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    let pad = w.find_data_pad(&element_name)?;
+
+    if probe_buffers(&pad, 1, DEFAULT_TIMEOUT).await?.is_empty() {
+        anyhow::bail!(
+            "No buffer flowed through {} after the gap within {} seconds",
+            element_name,
+            DEFAULT_TIMEOUT.as_secs()
+        );
+    }
+
+    w.check_no_errors()
+}
+
+/// Send an EOS event to the pipeline and check every sink receives exactly one
+/// EOS event within the given time, catching branches swallowing it.
+#[then(expr = "All sinks receive EOS within {word} {word}")]
+async fn check_all_sinks_eos(w: &mut World, value: u64, unit: String) -> Result<(), anyhow::Error> {
+    let timeout = parse_duration(value, &unit)?;
+    let sinks = w.pipeline_sinks()?;
+    if sinks.is_empty() {
+        anyhow::bail!("Pipeline has no sink");
+    }
+
+    let mut counters = Vec::new();
+    for sink in sinks {
+        for pad in sink.sink_pads() {
+            let count = Arc::new(AtomicUsize::new(0));
+            let probe_id = pad
+                .add_probe(gst::PadProbeType::EVENT_DOWNSTREAM, {
+                    let count = count.clone();
+                    move |_, info| {
+                        if let Some(gst::PadProbeData::Event(event)) = &info.data {
+                            if event.type_() == gst::EventType::Eos {
+                                count.fetch_add(1, Ordering::SeqCst);
+                            }
+                        }
+
+                        gst::PadProbeReturn::Ok
+                    }
+                })
+                .ok_or_else(|| anyhow::anyhow!("Could not add an EOS probe on {}", sink.name()))?;
+            counters.push((
+                format!("{}:{}", sink.name(), pad.name()),
+                pad,
+                probe_id,
+                count,
+            ));
+        }
+    }
+
+    w.get_pipeline()?.send_event(gst::event::Eos::new());
+
+    let start = Instant::now();
+    while start.elapsed() < timeout {
+        if counters
+            .iter()
+            .all(|(_, _, _, count)| count.load(Ordering::SeqCst) > 0)
+        {
+            break;
+        }
+
+        task::sleep(Duration::from_millis(50)).await;
+    }
+
+    let mut failures = Vec::new();
+    for (name, pad, probe_id, count) in counters {
+        pad.remove_probe(probe_id);
+        match count.load(Ordering::SeqCst) {
+            1 => (),
+            0 => failures.push(format!("{} received no EOS", name)),
+            n => failures.push(format!("{} received {} EOS", name, n)),
+        }
+    }
+
+    if !failures.is_empty() {
+        anyhow::bail!(
+            "Not all sinks received EOS within {} {}: {}",
+            value,
+            unit,
+            failures.join(", ")
+        );
+    }
+
+    Ok(())
+}
+
+/// Wait for the pipeline to reach EOS, seek back to the start and check the
+/// stream gets played again: buffers reach all sinks and EOS is reached once
+/// more.
+#[then(expr = "The pipeline can replay after EOS")]
+async fn check_replay_after_eos(w: &mut World) -> Result<(), anyhow::Error> {
+    let is_eos = |msg: &gst::Message| matches!(msg.view(), gst::MessageView::Eos(..));
+    if w.wait_for_message(DEFAULT_TIMEOUT, is_eos).await.is_none() {
+        anyhow::bail!(
+            "Pipeline did not reach EOS after {} seconds",
+            DEFAULT_TIMEOUT.as_secs()
+        );
+    }
+
+    let mut counters = Vec::new();
+    for sink in w.pipeline_sinks()? {
+        for pad in sink.sink_pads() {
+            let count = Arc::new(AtomicUsize::new(0));
+            let probe_id = pad
+                .add_probe(
+                    gst::PadProbeType::BUFFER | gst::PadProbeType::BUFFER_LIST,
+                    {
+                        let count = count.clone();
+                        move |_, _| {
+                            count.fetch_add(1, Ordering::SeqCst);
+                            gst::PadProbeReturn::Ok
+                        }
+                    },
+                )
+                .ok_or_else(|| {
+                    anyhow::anyhow!("Could not add a buffer probe on {}", sink.name())
+                })?;
+            counters.push((
+                format!("{}:{}", sink.name(), pad.name()),
+                pad,
+                probe_id,
+                count,
+            ));
+        }
+    }
+
+    let replay_start = w.messages.lock().unwrap().len();
+    let pipeline = w.get_pipeline()?;
+    pipeline.seek_simple(
+        gst::SeekFlags::FLUSH | gst::SeekFlags::KEY_UNIT,
+        gst::ClockTime::ZERO,
+    )?;
+    w.set_pipeline_state("play".to_string()).await?;
+
+    let start = Instant::now();
+    let replayed = loop {
+        w.check_no_errors()?;
+        if w.messages.lock().unwrap()[replay_start..]
+            .iter()
+            .any(is_eos)
+        {
+            break true;
+        }
+
+        if start.elapsed() >= DEFAULT_TIMEOUT {
+            break false;
+        }
+
+        task::sleep(Duration::from_millis(100)).await;
+    };
+
+    let mut starved = Vec::new();
+    for (name, pad, probe_id, count) in counters {
+        pad.remove_probe(probe_id);
+        if count.load(Ordering::SeqCst) == 0 {
+            starved.push(name);
+        }
+    }
+
+    if !starved.is_empty() {
+        anyhow::bail!(
+            "No buffer reached {:?} after seeking back to the start",
+            starved
+        );
+    }
+    if !replayed {
+        anyhow::bail!(
+            "Pipeline did not reach EOS again {} seconds after seeking back to the start",
+            DEFAULT_TIMEOUT.as_secs()
+        );
+    }
+
+    Ok(())
+}
+
+#[then(expr = "Pipeline '{word}' and '{word}' positions stay within {word} {word}")]
+fn check_pipelines_sync(
+    w: &mut World,
+    first: String,
+    second: String,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let max_divergence =
+        gst::ClockTime::from_nseconds(parse_duration(value, &unit)?.as_nanos() as u64);
+
+    let position = |name: &str| -> Result<gst::ClockTime, anyhow::Error> {
+        w.get_named_pipeline(name)?
+            .query_position::<gst::ClockTime>()
+            .ok_or_else(|| anyhow::anyhow!("Position query on pipeline {} not answered", name))
+    };
+    let first_position = position(&first)?;
+    let second_position = position(&second)?;
+
+    let divergence = clock_time_diff(first_position, second_position);
+    if divergence > max_divergence {
+        anyhow::bail!(
+            "Pipelines {} and {} diverge by {} ({} vs {})",
+            first,
+            second,
+            divergence,
+            first_position,
+            second_position
+        );
+    }
+
+    Ok(())
+}
+
+/// Stop the named pipeline, draining it first, while the other pipelines keep
+/// running.
+#[when(expr = "I stop pipeline '{word}'")]
+async fn stop_named_pipeline(w: &mut World, name: String) -> Result<(), anyhow::Error> {
+    w.change_state(w.get_named_pipeline(&name)?, gst::State::Null)
+        .await
+}
+
+/// Compare the time of the clock selected by the pipeline with the time of
+/// the system clock. Only meaningful once the pipeline selected a clock, when
+/// going to PLAYING.
+#[then(expr = "The pipeline clock matches the system clock within {word} {word}")]
+fn check_clock_drift(w: &mut World, value: u64, unit: String) -> Result<(), anyhow::Error> {
+    let max_drift = gst::ClockTime::from_nseconds(parse_duration(value, &unit)?.as_nanos() as u64);
+
+    let clock = w
+        .get_pipeline()?
+        .clock()
+        .ok_or_else(|| anyhow::anyhow!("Pipeline has no clock selected yet"))?;
+    let pipeline_time = clock
+        .time()
+        .ok_or_else(|| anyhow::anyhow!("Pipeline clock {} has no time", clock.name()))?;
+    let system_time = gst::SystemClock::obtain()
+        .time()
+        .ok_or_else(|| anyhow::anyhow!("System clock has no time"))?;
+
+    let drift = clock_time_diff(pipeline_time, system_time);
+    if drift > max_drift {
+        anyhow::bail!(
+            "Pipeline clock {} drifted by {} from the system clock",
+            clock.name(),
+            drift
+        );
+    }
+
+    Ok(())
+}
+
+/// Configure the named `queue2` for one of the playbin buffering modes:
+/// `stream` buffers in memory, `download` buffers the whole stream to a
+/// temporary file and `timeshift` to a temporary ring buffer file.
+#[when(expr = "I set buffering mode on {word} to {word}")]
+fn set_buffering_mode(
+    w: &mut World,
+    queue_name: String,
+    mode: String,
+) -> Result<(), anyhow::Error> {
+    let queue = w.find_element(&queue_name)?;
+    if queue.factory().is_none_or(|f| f.name() != "queue2") {
+        anyhow::bail!("Element {} is not a queue2", queue_name);
+    }
+
+    let temp_template = std::env::temp_dir()
+        .join("gst-cucumber-XXXXXX")
+        .to_str()
+        .map(String::from);
+    let (temp_template, ring_buffer_max_size) = match mode.as_str() {
+        "stream" => (None, 0),
+        "download" => (temp_template, 0),
+        "timeshift" => (temp_template, TIMESHIFT_RING_BUFFER_SIZE),
+        _ => anyhow::bail!(
+            "Invalid buffering mode: {} only [stream, download, timeshift] are supported",
+            mode
+        ),
+    };
+
+    let set = |name: &str, value: glib::Value| -> Result<(), anyhow::Error> {
+        let (pspec, obj) = w.find_element_property(&format!("{}::{}", queue_name, name))?;
+        obj.set_property_from_value(pspec.name(), &value);
+        Ok(())
+    };
+
+    gst::debug!(CAT, "Setting buffering mode on {} to {}", queue_name, mode);
+    set("use-buffering", true.to_value())?;
+    set("temp-template", temp_template.to_value())?;
+    set("ring-buffer-max-size", ring_buffer_max_size.to_value())
+}
+
+/// Buffering percentages notified on the bus so far.
+fn buffering_percents(w: &World) -> Vec<i32> {
+    w.messages
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|msg| match msg.view() {
+            gst::MessageView::Buffering(buffering) => Some(buffering.percent()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Wait for buffering to complete and check the buffering percentages notified
+/// until then never decreased.
+#[then(expr = "Buffering progresses monotonically on the pipeline")]
+async fn check_buffering_progress(w: &mut World) -> Result<(), anyhow::Error> {
+    let start = Instant::now();
+    let percents = loop {
+        let percents = buffering_percents(w);
+        if let Some(done) = percents.iter().position(|percent| *percent >= 100) {
+            break percents[..=done].to_vec();
+        }
+
+        if start.elapsed() >= DEFAULT_TIMEOUT {
+            anyhow::bail!(
+                "Buffering not complete after {} seconds, buffering percents: {:?}",
+                DEFAULT_TIMEOUT.as_secs(),
+                percents
+            );
+        }
+
+        task::sleep(Duration::from_millis(100)).await;
+    };
+
+    if let Some(i) = percents.windows(2).position(|pair| pair[1] < pair[0]) {
+        anyhow::bail!(
+            "Buffering went back from {}% to {}%, buffering percents: {:?}",
+            percents[i],
+            percents[i + 1],
+            percents
+        );
+    }
+
+    Ok(())
+}
+
+/// Check the named element has exactly the given comma-separated list of
+/// properties, inherited ones included. This is a contract test catching
+/// properties accidentally added to or removed from custom elements.
+#[then(regex = r"^(\S+) has exactly these properties: (.*)$")]
+fn check_property_list(
+    w: &mut World,
+    element_name: String,
+    properties: String,
+) -> Result<(), anyhow::Error> {
+    let element = w.find_element(&element_name)?;
+
+    let expected = properties
+        .split(',')
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .collect::<BTreeSet<_>>();
+    let actual = element
+        .list_properties()
+        .iter()
+        .map(|pspec| pspec.name().to_string())
+        .collect::<BTreeSet<_>>();
+
+    if actual != expected {
+        anyhow::bail!(
+            "Properties of {} differ, unexpected: {:?}, missing: {:?}",
+            element_name,
+            actual.difference(&expected).collect::<Vec<_>>(),
+            expected.difference(&actual).collect::<Vec<_>>()
+        );
+    }
+
+    Ok(())
+}
+
+/// Load a preset, e.g. `When I load preset 'fast' on encoder`, on an element
+/// implementing the `GstPreset` interface.
+#[when(regex = r"I load preset '(\S+)' on (\S+)$")]
+fn load_preset(
+    w: &mut World,
+    preset_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let element = w.find_element(&element_name)?;
+    let preset = element
+        .dynamic_cast_ref::<gst::Preset>()
+        .ok_or_else(|| anyhow::anyhow!("Element {} doesn't implement GstPreset", element_name))?;
+
+    gst::debug!(CAT, "Loading preset {} on {}", preset_name, element_name);
+    preset.load_preset(&preset_name).map_err(|_| {
+        anyhow::anyhow!(
+            "Could not load preset {} on {}, available presets: {:?}",
+            preset_name,
+            element_name,
+            preset.preset_names()
+        )
+    })?;
+
+    w.loaded_presets.insert(element_name, preset_name);
+    Ok(())
+}
+
+#[then(expr = "The preset on {word} set {word} to {word}")]
+fn check_preset_property(
+    w: &mut World,
+    element_name: String,
+    property_name: String,
+    value: String,
+) -> Result<(), anyhow::Error> {
+    let preset_name = w
+        .loaded_presets
+        .get(&element_name)
+        .ok_or_else(|| anyhow::anyhow!("No preset loaded on {}", element_name))?;
+
+    let (pspec, obj) = w.find_element_property(&format!("{}::{}", element_name, property_name))?;
+    let expected = glib::Value::deserialize(&value, pspec.value_type())
+        .map_err(|_| anyhow::anyhow!("Invalid value for {}: {}", property_name, value))?;
+    let actual = obj.property_value(pspec.name());
+
+    if expected.compare(&actual) != Some(cmp::Ordering::Equal) {
+        anyhow::bail!(
+            "Preset {} set {}::{} to {} instead of {}",
+            preset_name,
+            element_name,
+            property_name,
+            actual
+                .serialize()
+                .map_or_else(|_| format!("{:?}", actual), |v| v.to_string()),
+            value
+        );
+    }
+
+    Ok(())
+}
+
+/// Tear the pipeline down abruptly, from PLAYING straight to NULL without
+/// draining it with an EOS event first, as when an application gets killed.
+/// Only the errors posted during the teardown are taken into account.
+#[then(expr = "The pipeline goes directly to NULL from PLAYING")]
+fn check_abrupt_teardown(w: &mut World) -> Result<(), anyhow::Error> {
+    let pipeline = w.get_pipeline()?;
+    let (_, current, _) = pipeline.state(gst::ClockTime::ZERO);
+    if current != gst::State::Playing {
+        anyhow::bail!("Pipeline is {:?}, not PLAYING", current);
+    }
+
+    let since = w.messages.lock().unwrap().len();
+    pipeline
+        .set_state(gst::State::Null)
+        .map_err(|_| anyhow::anyhow!("Unable to set pipeline state to NULL from PLAYING"))?;
+
+    let (_, current, _) = pipeline.state(gst::ClockTime::ZERO);
+    if current != gst::State::Null {
+        anyhow::bail!("Pipeline is {:?} after being torn down", current);
+    }
+
+    w.check_no_errors_since(since)
+}
+
+/// The pads of `element` created from request pad templates.
+fn request_pads(element: &gst::Element) -> Vec<gst::Pad> {
+    element
+        .pads()
+        .into_iter()
+        .filter(|pad| {
+            pad.pad_template()
+                .is_some_and(|templ| templ.presence() == gst::PadPresence::Request)
+        })
+        .collect()
+}
+
+/// Stop the pipeline and check the named element released the request pads
+/// it got while running. Request pads it already had before the pipeline
+/// started, e.g. the ones requested when linking it in the pipeline
+/// description, are left alone.
+#[then(expr = "No request pads remain after stopping {word}")]
+async fn check_request_pads_released(
+    w: &mut World,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let element = w.find_element(&element_name)?;
+    w.set_pipeline_state("stop".to_string()).await?;
+
+    let initial_request_pads = w.initial_request_pads.lock().unwrap();
+    let remaining = request_pads(&element)
+        .into_iter()
+        .filter(|pad| !initial_request_pads.contains(pad))
+        .map(|pad| pad.name())
+        .collect::<Vec<_>>();
+    if !remaining.is_empty() {
+        anyhow::bail!(
+            "Request pads of {} not released after stopping: {:?}",
+            element_name,
+            remaining
+        );
+    }
+
+    Ok(())
+}
+
+/// Measure the rate at which the named element consumes its input, as the
+/// media duration of the buffers it received over the wall-clock time they
+/// took to arrive, and compare it with the expected factor of realtime, 10%
+/// off at most. The measured factor is stored in `extra_data` as
+/// `<element>-realtime-factor`.
+#[then(expr = "{word} consumes input at {word}x realtime")]
+async fn check_realtime_factor(
+    w: &mut World,
+    element_name: String,
+    expected: f64,
+) -> Result<(), anyhow::Error> {
+    let pad = w.find_data_pad(&element_name)?;
+
+    let timings = probe_buffer_timings(&pad, PROBED_BUFFERS_COUNT, DEFAULT_TIMEOUT).await?;
+    let timed = timings
+        .iter()
+        .filter_map(|timing| timing.pts.map(|pts| (pts, timing.arrival)))
+        .collect::<Vec<_>>();
+    let ((first_pts, first_arrival), (last_pts, last_arrival)) = match (timed.first(), timed.last())
+    {
+        (Some(first), Some(last)) if last.0 > first.0 => (*first, *last),
+        _ => anyhow::bail!(
+            "Not enough timestamped buffers flowed through {} to measure its rate",
+            element_name
+        ),
+    };
+
+    let media_duration = (last_pts - first_pts).nseconds() as f64;
+    let wall_duration = last_arrival
+        .saturating_duration_since(first_arrival)
+        .as_nanos() as f64;
+    let factor = media_duration / wall_duration;
+    w.extra_data
+        .set(&format!("{}-realtime-factor", element_name), factor);
+
+    if (factor - expected).abs() > expected * RATE_TOLERANCE {
+        anyhow::bail!(
+            "{} consumes input at {:.2}x realtime instead of {}x",
+            element_name,
+            factor,
+            expected
+        );
+    }
+
+    Ok(())
+}
+
+#[then(expr = "Property {word} is controllable")]
+fn check_property_controllable(w: &mut World, propname: String) -> Result<(), anyhow::Error> {
+    let (pspec, _) = w.find_element_property(&propname)?;
+
+    if !pspec.flags().contains(gst::PARAM_FLAG_CONTROLLABLE) {
+        anyhow::bail!(
+            "Property {} is not controllable, flags: {:?}",
+            propname,
+            pspec.flags()
+        );
+    }
+
+    Ok(())
+}
+
+/// Animate a controllable property linearly between two values, starting from
+/// the current pipeline position, with an interpolation control source bound
+/// to it. The element needs to synchronize its controlled properties, as most
+/// sources and filters do while processing buffers.
+#[when(expr = "I animate {word} from {word} to {word} over {word} {word}")]
+#[cfg(feature = "controller")]
+fn animate_property(
+    w: &mut World,
+    propname: String,
+    from: f64,
+    to: f64,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let duration = parse_duration(value, &unit)?;
+    let (pspec, obj) = w.find_element_property(&propname)?;
+    if !pspec.flags().contains(gst::PARAM_FLAG_CONTROLLABLE) {
+        anyhow::bail!("Property {} is not controllable", propname);
+    }
+
+    let object = obj
+        .downcast::<gst::Object>()
+        .map_err(|_| anyhow::anyhow!("Property {} is not on a GstObject", propname))?;
+    let source = gstcontroller::InterpolationControlSource::new();
+    source.set_property("mode", gstcontroller::InterpolationMode::Linear);
+    let binding = gstcontroller::DirectControlBinding::new_absolute(&object, pspec.name(), &source);
+    object.add_control_binding(&binding)?;
+
+    let position = w
+        .get_pipeline()?
+        .query_position::<gst::ClockTime>()
+        .unwrap_or(gst::ClockTime::ZERO);
+    let end = position + gst::ClockTime::from_nseconds(duration.as_nanos() as u64);
+    if !source.set(position, from) || !source.set(end, to) {
+        anyhow::bail!("Could not set control points on {}", propname);
+    }
+
+    gst::debug!(
+        CAT,
+        "Animating {} from {} to {} between {} and {}",
+        propname,
+        from,
+        to,
+        position,
+        end
+    );
+    w.animations.insert(
+        propname,
+        Animation {
+            from,
+            to,
+            start: Instant::now(),
+            duration,
+        },
+    );
+    Ok(())
+}
+
+/// Wait until the middle of the animation of a property and check its value
+/// is then strictly between the animation endpoints.
+#[then(expr = "Property {word} is animated")]
+#[cfg(feature = "controller")]
+async fn check_property_animated(w: &mut World, propname: String) -> Result<(), anyhow::Error> {
+    let animation = w
+        .animations
+        .get(&propname)
+        .ok_or_else(|| anyhow::anyhow!("Property {} is not animated", propname))?;
+    let (low, high) = (
+        animation.from.min(animation.to),
+        animation.from.max(animation.to),
+    );
+
+    let middle = animation.start + animation.duration / 2;
+    task::sleep(middle.saturating_duration_since(Instant::now())).await;
+
+    let (pspec, obj) = w.find_element_property(&propname)?;
+    let value = value_as_f64(&obj.property_value(pspec.name()))
+        .ok_or_else(|| anyhow::anyhow!("Property {} is not numeric", propname))?;
+    if value <= low || value >= high {
+        anyhow::bail!(
+            "Property {} is {} in the middle of its animation, not between {} and {}",
+            propname,
+            value,
+            low,
+            high
+        );
+    }
+
+    Ok(())
+}
+
+/// Write the messages posted on the bus so far to a file, one per line along
+/// with the pipeline running time they were posted at, their type and source.
+/// Relative paths are resolved from the directory of the feature file.
+#[when(regex = r"Log the bus timeline to '(.*)'$")]
+fn log_bus_timeline(w: &mut World, path: String) -> Result<(), anyhow::Error> {
+    let path = w.resolve_path(&path);
+
+    let messages = w.messages.lock().unwrap();
+    let running_times = w.message_running_times.lock().unwrap();
+    let timeline = messages
+        .iter()
+        .zip(running_times.iter())
+        .map(|(msg, running_time)| {
+            format!(
+                "{} {:?} {} {}\n",
+                running_time.map_or_else(|| "none".to_string(), |t| t.to_string()),
+                msg.type_(),
+                msg.src()
+                    .map_or_else(|| "none".to_string(), |src| src.path_string().to_string()),
+                msg.structure().map_or_else(String::new, |s| s.to_string())
+            )
+        })
+        .collect::<String>();
+
+    gst::debug!(CAT, "Logging bus timeline to {}", path.display());
+    std::fs::write(&path, timeline)
+        .map_err(|err| anyhow::anyhow!("Could not write {}: {}", path.display(), err))
+}
+
+/// Count the audio samples, per channel, flowing through the named element
+/// until the expected number is reached.
+#[then(expr = "At least {int} audio samples passed {word}")]
+#[cfg(feature = "audio")]
+async fn check_audio_samples(
+    w: &mut World,
+    expected: u64,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let pad = w.find_data_pad(&element_name)?;
+
+    let (sender, mut receiver) = mpsc::unbounded();
+    let probe_id = pad
+        .add_probe(
+            gst::PadProbeType::BUFFER | gst::PadProbeType::BUFFER_LIST,
+            move |pad, info| {
+                let size = match &info.data {
+                    Some(gst::PadProbeData::Buffer(buffer)) => buffer.size(),
+                    Some(gst::PadProbeData::BufferList(list)) => list.calculate_size(),
+                    _ => 0,
+                };
+                let info = pad
+                    .current_caps()
+                    .and_then(|caps| gstaudio::AudioInfo::from_caps(&caps).ok());
+                let _ = sender.unbounded_send(info.map(|info| (size / info.bpf() as usize) as u64));
+
+                gst::PadProbeReturn::Ok
+            },
+        )
+        .ok_or_else(|| anyhow::anyhow!("Could not add a buffer probe on {}", element_name))?;
+
+    let deadline = Instant::now() + DEFAULT_TIMEOUT;
+    let mut total = 0;
+    let mut result = Ok(());
+    while total < expected {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        match future::timeout(remaining, receiver.next()).await {
+            Ok(Some(Some(samples))) => total += samples,
+            Ok(Some(None)) => {
+                result = Err(anyhow::anyhow!(
+                    "No raw audio caps negotiated on {}, current caps: {:?}",
+                    element_name,
+                    pad.current_caps()
+                ));
+                break;
+            }
+            _ => break,
+        }
+    }
+    pad.remove_probe(probe_id);
+    result?;
+
+    if total < expected {
+        anyhow::bail!(
+            "Only {} audio samples out of {} passed {} after {} seconds",
+            total,
+            expected,
+            element_name,
+            DEFAULT_TIMEOUT.as_secs()
+        );
+    }
+
+    Ok(())
+}
+
+/// Text of the errors posted on the bus so far.
+fn posted_errors(w: &World) -> Vec<String> {
+    w.messages
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|msg| match msg.view() {
+            gst::MessageView::Error(err) => Some(err.error().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether `err` reports a negotiation failure, either as a core negotiation
+/// error or as a streaming error caused by a `not-negotiated` flow return.
+fn is_negotiation_error(err: &gst::message::Error) -> bool {
+    err.error().matches(gst::CoreError::Negotiation)
+        || err
+            .debug()
+            .is_some_and(|debug| debug.contains("not-negotiated"))
+}
+
+#[then(expr = "The pipeline reports a not-negotiated error")]
+async fn check_not_negotiated_error(w: &mut World) -> Result<(), anyhow::Error> {
+    let msg = w
+        .wait_for_message(
+            DEFAULT_TIMEOUT,
+            |msg| matches!(msg.view(), gst::MessageView::Error(err) if is_negotiation_error(err)),
+        )
+        .await;
+
+    if msg.is_none() {
+        anyhow::bail!(
+            "No not-negotiated error posted after {} seconds, errors: {:?}",
+            DEFAULT_TIMEOUT.as_secs(),
+            posted_errors(w)
+        );
+    }
+
+    Ok(())
+}
+
+/// Check the pipeline position, e.g. `Then Pipeline position is 5sec` after
+/// a seek, 100ms off at most.
+#[then(expr = "Pipeline position is {word}")]
+fn check_position(w: &mut World, position: String) -> Result<(), anyhow::Error> {
+    let expected = gst::ClockTime::from_nseconds(parse_duration_str(&position)?.as_nanos() as u64);
+
+    let mut query = gst::query::Position::new(gst::Format::Time);
+    if !w.get_pipeline()?.query(&mut query) {
+        anyhow::bail!("Position query not answered by the pipeline");
+    }
+    let actual = match query.result() {
+        gst::GenericFormattedValue::Time(Some(actual)) => actual,
+        result => anyhow::bail!("Pipeline answered an invalid position: {:?}", result),
+    };
+
+    if clock_time_diff(actual, expected) > POSITION_TOLERANCE {
+        anyhow::bail!("Pipeline position is {} instead of {}", actual, expected);
+    }
+
+    Ok(())
+}
+
+/// Check the duration of the media, e.g. `Then Pipeline duration is 30sec`,
+/// 100ms off at most. The pipeline needs to be at least prerolled.
+#[then(expr = "Pipeline duration is {word}")]
+fn check_duration(w: &mut World, duration: String) -> Result<(), anyhow::Error> {
+    let expected = gst::ClockTime::from_nseconds(parse_duration_str(&duration)?.as_nanos() as u64);
+
+    let mut query = gst::query::Duration::new(gst::Format::Time);
+    if !w.get_pipeline()?.query(&mut query) {
+        anyhow::bail!("Duration query not answered by the pipeline, is it paused or playing?");
+    }
+    let actual = match query.result() {
+        gst::GenericFormattedValue::Time(Some(actual)) => actual,
+        gst::GenericFormattedValue::Time(None) => {
+            anyhow::bail!("Duration not known yet, pause the pipeline first")
+        }
+        result => anyhow::bail!("Pipeline answered an invalid duration: {:?}", result),
+    };
+
+    if clock_time_diff(actual, expected) > POSITION_TOLERANCE {
+        anyhow::bail!("Pipeline duration is {} instead of {}", actual, expected);
+    }
+
+    Ok(())
+}
+
+/// Perform an accurate flushing seek to the given position, e.g.
+/// `When I seek to 5sec`, and wait for the pipeline to complete it.
+#[when(expr = "I seek to {word}")]
+async fn seek(w: &mut World, position: String) -> Result<(), anyhow::Error> {
+    let target = gst::ClockTime::from_nseconds(parse_duration_str(&position)?.as_nanos() as u64);
+
+    gst::debug!(CAT, "Seeking to {}", target);
+    w.seek_and_wait(gst::event::Seek::new(
+        1.,
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+        gst::SeekType::Set,
+        target,
+        gst::SeekType::None,
+        gst::ClockTime::NONE,
+    ))
+    .await
+}
+
+/// Change the playback rate, keeping the current position: forward rates play
+/// from the current position, negative ones play backwards from it to the
+/// start, e.g. `When I set playback rate to -2.0`.
+#[when(expr = "I set playback rate to {word}")]
+async fn set_playback_rate(w: &mut World, rate: String) -> Result<(), anyhow::Error> {
+    let rate = rate
+        .parse::<f64>()
+        .map_err(|_| anyhow::anyhow!("Invalid playback rate: {}", rate))?;
+    if rate == 0. {
+        anyhow::bail!("Invalid playback rate: 0");
+    }
+
+    let position = w
+        .get_pipeline()?
+        .query_position::<gst::ClockTime>()
+        .ok_or_else(|| anyhow::anyhow!("Position query not answered by the pipeline"))?;
+    let flags = gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE;
+    let seek = if rate > 0. {
+        gst::event::Seek::new(
+            rate,
+            flags,
+            gst::SeekType::Set,
+            position,
+            gst::SeekType::None,
+            gst::ClockTime::NONE,
+        )
+    } else {
+        gst::event::Seek::new(
+            rate,
+            flags,
+            gst::SeekType::Set,
+            gst::ClockTime::ZERO,
+            gst::SeekType::Set,
+            position,
+        )
+    };
+
+    gst::debug!(CAT, "Setting playback rate to {} at {}", rate, position);
+    w.seek_and_wait(seek).await
+}
+
+/// Wait for the given time and check the pipeline running time advanced
+/// accordingly, 10% plus 100ms off at most, catching stalled clocks.
+#[then(expr = "The pipeline running time advances over {word} {word}")]
+async fn check_running_time_advances(
+    w: &mut World,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let wait = parse_duration(value, &unit)?;
+    let running_time = |w: &World| -> Result<gst::ClockTime, anyhow::Error> {
+        w.get_pipeline()?
+            .current_running_time()
+            .ok_or_else(|| anyhow::anyhow!("Pipeline has no running time, is it PLAYING?"))
+    };
+
+    let before = running_time(w)?;
+    task::sleep(wait).await;
+    let after = running_time(w)?;
+
+    let expected = gst::ClockTime::from_nseconds(wait.as_nanos() as u64);
+    let advance = after.saturating_sub(before);
+    let tolerance =
+        gst::ClockTime::from_nseconds((expected.nseconds() as f64 * RATE_TOLERANCE) as u64)
+            + POSITION_TOLERANCE;
+    if clock_time_diff(advance, expected) > tolerance {
+        anyhow::bail!(
+            "Running time went from {} to {} over {} {}",
+            before,
+            after,
+            value,
+            unit
+        );
+    }
+
+    Ok(())
+}
+
+/// Pause the pipeline, seek to the middle of the stream, or to 1 second when
+/// its duration is unknown, and check both the pipeline position and the frame
+/// the named sink prerolled reflect the seek target. The sink needs
+/// `enable-last-sample` set.
+#[then(expr = "{word} handles a flushing seek while paused")]
+async fn check_paused_seek(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    w.set_pipeline_state("pause".to_string()).await?;
+    let pipeline = w.get_pipeline()?;
+    let (res, _, _) = pipeline.state(gst::ClockTime::from_nseconds(
+        DEFAULT_TIMEOUT.as_nanos() as u64
+    ));
+    res.map_err(|_| anyhow::anyhow!("Pipeline failed to preroll"))?;
+
+    let target = pipeline
+        .query_duration::<gst::ClockTime>()
+        .map_or(gst::ClockTime::SECOND, |duration| duration / 2);
+    gst::debug!(CAT, "Seeking to {} while paused", target);
+    w.seek_and_wait(gst::event::Seek::new(
+        1.,
+        gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+        gst::SeekType::Set,
+        target,
+        gst::SeekType::None,
+        gst::ClockTime::NONE,
+    ))
+    .await?;
+
+    let position = w
+        .get_pipeline()?
+        .query_position::<gst::ClockTime>()
+        .ok_or_else(|| anyhow::anyhow!("Position query not answered by the pipeline"))?;
+    if clock_time_diff(position, target) > POSITION_TOLERANCE {
+        anyhow::bail!(
+            "Pipeline position is {} after seeking to {}",
+            position,
+            target
+        );
+    }
+
+    let sample = get_last_frame(w, &element_name)?
+        .ok_or_else(|| anyhow::anyhow!("{} did not preroll after the seek", element_name))?;
+    let stream_time = sample
+        .segment()
+        .and_then(|segment| segment.downcast_ref::<gst::ClockTime>())
+        .zip(sample.buffer().and_then(|buffer| buffer.pts()))
+        .and_then(|(segment, pts)| segment.to_stream_time(pts))
+        .ok_or_else(|| anyhow::anyhow!("Frame prerolled by {} has no timestamp", element_name))?;
+    if clock_time_diff(stream_time, target) > POSITION_TOLERANCE {
+        anyhow::bail!(
+            "{} prerolled a frame at {} after seeking to {}",
+            element_name,
+            stream_time,
+            target
+        );
+    }
+
+    Ok(())
+}
+
+#[then(expr = "Element {word} exists")]
+fn check_element_exists(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    if w.find_element(&element_name).is_err() {
+        anyhow::bail!(
+            "Could not find element {}, pipeline elements: {:?}",
+            element_name,
+            w.pipeline_elements()?
+                .iter()
+                .map(|element| element.name())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    Ok(())
+}
+
+/// Check an element got removed from the pipeline, e.g. by a third-party step
+/// dynamically modifying it.
+#[then(expr = "The pipeline no longer contains {word}")]
+fn check_element_removed(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    if let Ok(element) = w.find_element(&element_name) {
+        anyhow::bail!("{} is still in the pipeline", element.path_string());
+    }
+
+    Ok(())
+}
+
+/// Context types requested with need-context messages so far.
+fn requested_context_types(w: &World) -> Vec<String> {
+    w.messages
+        .lock()
+        .unwrap()
+        .iter()
+        .filter_map(|msg| match msg.view() {
+            gst::MessageView::NeedContext(need) => Some(need.context_type().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Check an element of the pipeline requested a context of the given type,
+/// e.g. `Then The pipeline needs a gst.gl.GLDisplay context`.
+#[then(expr = "The pipeline needs a {word} context")]
+async fn check_needs_context(w: &mut World, context_type: String) -> Result<(), anyhow::Error> {
+    let msg = w
+        .wait_for_message(DEFAULT_TIMEOUT, |msg| match msg.view() {
+            gst::MessageView::NeedContext(need) => need.context_type() == context_type,
+            _ => false,
+        })
+        .await;
+
+    if msg.is_none() {
+        anyhow::bail!(
+            "No {} context requested after {} seconds, requested contexts: {:?}",
+            context_type,
+            DEFAULT_TIMEOUT.as_secs(),
+            requested_context_types(w)
+        );
+    }
+
+    Ok(())
+}
+
+/// Answer need-context messages of the given type with the context prepared
+/// with `World::add_context()`. Contexts get set from the bus sync handler, in
+/// the thread of the element requesting it, as elements query their context
+/// synchronously and would otherwise miss it.
+#[given(regex = r"I provide context '(\S+)' on need-context$")]
+fn provide_context(w: &mut World, context_type: String) -> Result<(), anyhow::Error> {
+    let context = w.contexts.get(&context_type).ok_or_else(|| {
+        anyhow::anyhow!(
+            "No {} context prepared, prepared contexts: {:?}",
+            context_type,
+            w.contexts.keys().collect::<Vec<_>>()
+        )
+    })?;
+
+    w.provided_contexts
+        .lock()
+        .unwrap()
+        .insert(context_type, context.clone());
+    Ok(())
+}
+
+/// Parse a framerate, either as a number of frames per second (`30`) or as a
+/// fraction (`30000/1001`).
+fn parse_framerate(framerate: &str) -> Result<gst::Fraction, anyhow::Error> {
+    let (num, den) = framerate.split_once('/').unwrap_or((framerate, "1"));
+    match (num.parse(), den.parse()) {
+        (Ok(num), Ok(den)) if den > 0 => Ok(gst::Fraction::new(num, den)),
+        _ => anyhow::bail!("Invalid framerate: {}", framerate),
+    }
+}
+
+/// Framerate negotiated on the named pad.
+fn negotiated_framerate(
+    w: &World,
+    pad_name: &str,
+    element_name: &str,
+) -> Result<gst::Fraction, anyhow::Error> {
+    negotiated_caps_structure(w, pad_name, element_name)?
+        .get::<gst::Fraction>("framerate")
+        .map_err(|_| anyhow::anyhow!("No framerate negotiated on {}:{}", element_name, pad_name))
+}
+
+#[then(expr = "videorate {word} outputs {word} fps from {word} fps input")]
+fn check_videorate(
+    w: &mut World,
+    element_name: String,
+    output: String,
+    input: String,
+) -> Result<(), anyhow::Error> {
+    let expected_output = parse_framerate(&output)?;
+    let expected_input = parse_framerate(&input)?;
+
+    let actual_input = negotiated_framerate(w, "sink", &element_name)?;
+    let actual_output = negotiated_framerate(w, "src", &element_name)?;
+    if actual_input != expected_input || actual_output != expected_output {
+        anyhow::bail!(
+            "{} outputs {} fps from {} fps input instead of {} fps from {} fps",
+            element_name,
+            actual_output,
+            actual_input,
+            expected_output,
+            expected_input
+        );
+    }
+
+    Ok(())
+}
+
+/// Wait for an error message whose text contains `pattern` to be posted on
+/// the bus.
+async fn wait_for_error(w: &World, pattern: &str) -> Result<(), anyhow::Error> {
+    let msg = w
+        .wait_for_message(DEFAULT_TIMEOUT, |msg| match msg.view() {
+            gst::MessageView::Error(err) => err.error().to_string().contains(pattern),
+            _ => false,
+        })
+        .await;
+
+    if msg.is_none() {
+        anyhow::bail!(
+            "No error matching '{}' posted after {} seconds, errors: {:?}",
+            pattern,
+            DEFAULT_TIMEOUT.as_secs(),
+            posted_errors(w)
+        );
+    }
+
+    Ok(())
+}
+
+#[then(expr = "The bus posted an error")]
+async fn check_error_posted(w: &mut World) -> Result<(), anyhow::Error> {
+    wait_for_error(w, "").await
+}
+
+#[then(expr = "The bus posted an error matching {string}")]
+async fn check_error_matching_posted(w: &mut World, pattern: String) -> Result<(), anyhow::Error> {
+    wait_for_error(w, &pattern).await
+}
+
+#[then(expr = "No error was posted")]
+fn check_no_error_posted(w: &mut World) -> Result<(), anyhow::Error> {
+    w.check_no_errors()
+}
+
+/// Check the named sink keeps rendering new frames, its last sample changing
+/// over time, rather than being stuck on its preroll frame. The sink needs
+/// `enable-last-sample` set.
+#[then(expr = "{word} is rendering")]
+async fn check_rendering(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    let last_buffer = |w: &World| -> Result<Option<gst::Buffer>, anyhow::Error> {
+        Ok(get_last_frame(w, &element_name)?.and_then(|sample| sample.buffer_owned()))
+    };
+
+    let first = last_buffer(w)?;
+    let start = Instant::now();
+    loop {
+        w.check_no_errors()?;
+        task::sleep(Duration::from_millis(100)).await;
+
+        if let Some(current) = last_buffer(w)? {
+            let changed = match &first {
+                Some(first) => first.as_ptr() != current.as_ptr() || first.pts() != current.pts(),
+                None => true,
+            };
+            if changed {
+                return Ok(());
+            }
+        }
+
+        if start.elapsed() >= DEFAULT_TIMEOUT {
+            anyhow::bail!(
+                "{} did not render a new frame after {} seconds",
+                element_name,
+                DEFAULT_TIMEOUT.as_secs()
+            );
+        }
+    }
+}
+
+/// Play the pipeline if it is not playing yet and wait for it to reach EOS,
+/// failing if an error is posted meanwhile. Only the messages posted after the
+/// step started are taken into account.
+#[then(expr = "The pipeline reached EOS within {word}")]
+async fn check_eos_reached(w: &mut World, timeout: String) -> Result<(), anyhow::Error> {
+    let timeout = parse_duration_str(&timeout)?;
+    let since = w.messages.lock().unwrap().len();
+    if w.get_pipeline()?.current_state() != gst::State::Playing {
+        w.set_pipeline_state("play".to_string()).await?;
+    }
+
+    let msg = w
+        .wait_for_message_since(since, timeout, |msg| {
+            matches!(
+                msg.view(),
+                gst::MessageView::Eos(..) | gst::MessageView::Error(..)
+            )
+        })
+        .await
+        .ok_or_else(|| anyhow::anyhow!("Pipeline did not reach EOS after {:?}", timeout))?;
+    if let gst::MessageView::Error(err) = msg.view() {
+        anyhow::bail!(
+            "Error from {:?} before reaching EOS: {} ({:?})",
+            err.src().map(|s| s.path_string()),
+            err.error(),
+            err.debug()
+        );
+    }
+
+    Ok(())
+}
+
+/// Check the standard deviation of the intervals between buffer arrivals on the
+/// named element. The measured jitter is recorded in `extra_data` as
+/// `<element>-buffer-arrival-jitter`.
+#[then(expr = "Buffer arrival jitter on {word} is below {word} {word}")]
+async fn check_arrival_jitter(
+    w: &mut World,
+    element_name: String,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let max_jitter = parse_duration(value, &unit)?;
+    let pad = w.find_data_pad(&element_name)?;
+
+    let arrivals = probe_pad(&pad, PROBED_BUFFERS_COUNT, DEFAULT_TIMEOUT, |_, arrival| {
+        arrival
+    })
+    .await?;
+    if arrivals.len() < 3 {
+        anyhow::bail!(
+            "Not enough buffers on {} to measure jitter: {}",
+            element_name,
+            arrivals.len()
+        );
+    }
+
+    let intervals = arrivals
+        .windows(2)
+        .map(|pair| pair[1].saturating_duration_since(pair[0]).as_secs_f64())
+        .collect::<Vec<_>>();
+    let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+    let variance = intervals
+        .iter()
+        .map(|interval| (interval - mean).powi(2))
+        .sum::<f64>()
+        / intervals.len() as f64;
+    let jitter = Duration::from_secs_f64(variance.sqrt());
+
+    w.extra_data.set(
+        &format!("{}-buffer-arrival-jitter", element_name),
+        gst::ClockTime::from_nseconds(jitter.as_nanos() as u64),
+    );
+
+    if jitter > max_jitter {
+        anyhow::bail!(
+            "Buffer arrival jitter on {} is {:?}, above {} {}",
+            element_name,
+            jitter,
+            value,
+            unit
+        );
+    }
+
+    Ok(())
+}
+
+/// Check some of the buffers flowing through the named element carry a
+/// reference timestamp meta, with a reference matching `reference` if set.
+async fn check_reference_timestamp_meta(
+    w: &World,
+    element_name: &str,
+    reference: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    let reference = reference
+        .map(|reference| {
+            gst::Caps::from_str(reference)
+                .map_err(|_| anyhow::anyhow!("Invalid reference caps: {}", reference))
+        })
+        .transpose()?;
+    let pad = w.find_data_pad(element_name)?;
+
+    let buffers = probe_pad(&pad, PROBED_BUFFERS_COUNT, DEFAULT_TIMEOUT, |buffer, _| {
+        buffer
+            .iter_meta::<gst::ReferenceTimestampMeta>()
+            .map(|meta| meta.reference().to_owned())
+            .collect::<Vec<_>>()
+    })
+    .await?;
+    let mut seen = BTreeSet::new();
+    for meta_references in &buffers {
+        for meta_reference in meta_references {
+            match &reference {
+                Some(reference) if !meta_reference.is_subset(reference) => {
+                    seen.insert(meta_reference.to_string());
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    match reference {
+        Some(reference) if !seen.is_empty() => anyhow::bail!(
+            "None of the {} buffers seen on {} carried a {} reference timestamp meta, only {:?}",
+            buffers.len(),
+            element_name,
+            reference,
+            seen
+        ),
+        _ => anyhow::bail!(
+            "None of the {} buffers seen on {} carried a reference timestamp meta",
+            buffers.len(),
+            element_name
+        ),
+    }
+}
+
+#[then(expr = "The RTP buffers on {word} carry reference timestamp meta")]
+async fn check_rtp_reference_timestamps(
+    w: &mut World,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    check_reference_timestamp_meta(w, &element_name, None).await
+}
+
+/// Same as above, also checking the reference caps of the meta, such as
+/// `timestamp/x-ntp`.
+#[then(regex = r"The RTP buffers on (\S+) carry reference timestamp meta for '(.*)'$")]
+async fn check_rtp_reference_timestamps_for(
+    w: &mut World,
+    element_name: String,
+    reference: String,
+) -> Result<(), anyhow::Error> {
+    check_reference_timestamp_meta(w, &element_name, Some(&reference)).await
+}
+
+/// Check the time each frame spends in the named decoder. Input and output
+/// buffers are correlated by their PTS: the arrival time of each buffer on the
+/// sink pad is recorded, and compared to the arrival time of the output buffer
+/// with the same PTS on the src pad. Output buffers whose PTS wasn't seen on
+/// input, e.g. when the parser upstream doesn't set it, are ignored. The worst
+/// and average latencies are recorded in `extra_data` as
+/// `decode-latency-max` and `decode-latency-average`.
+#[then(expr = "Per-frame decode latency on {word} is below {word} {word}")]
+async fn check_decode_latency(
+    w: &mut World,
+    element_name: String,
+    value: u64,
+    unit: String,
+) -> Result<(), anyhow::Error> {
+    let max_latency = parse_duration(value, &unit)?;
+    let sinkpad = w.find_pad("sink", &element_name)?;
+    let srcpad = w.find_pad("src", &element_name)?;
+
+    let inputs = Arc::new(Mutex::new(HashMap::new()));
+    let probe_id = sinkpad
+        .add_probe(gst::PadProbeType::BUFFER, {
+            let inputs = inputs.clone();
+            move |_, info| {
+                if let Some(gst::PadProbeData::Buffer(buffer)) = &info.data {
+                    if let Some(pts) = buffer.pts() {
+                        inputs.lock().unwrap().insert(pts, Instant::now());
+                    }
+                }
+
+                gst::PadProbeReturn::Ok
+            }
+        })
+        .ok_or_else(|| anyhow::anyhow!("Could not add a buffer probe on {}", element_name))?;
+
+    let outputs = probe_buffer_timings(&srcpad, PROBED_BUFFERS_COUNT, DEFAULT_TIMEOUT).await;
+    sinkpad.remove_probe(probe_id);
+
+    let inputs = inputs.lock().unwrap();
+    let latencies = outputs?
+        .into_iter()
+        .filter_map(|output| {
+            let pts = output.pts?;
+            let input = inputs.get(&pts)?;
+            Some((pts, output.arrival.saturating_duration_since(*input)))
+        })
+        .collect::<Vec<_>>();
+
+    let (worst_pts, worst) = latencies
+        .iter()
+        .max_by_key(|(_, latency)| *latency)
+        .copied()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Could not correlate any input and output buffers of {}",
+                element_name
+            )
+        })?;
+    let average = latencies
+        .iter()
+        .map(|(_, latency)| *latency)
+        .sum::<Duration>()
+        / latencies.len() as u32;
+
+    w.extra_data.set(
+        "decode-latency-max",
+        gst::ClockTime::from_nseconds(worst.as_nanos() as u64),
+    );
+    w.extra_data.set(
+        "decode-latency-average",
+        gst::ClockTime::from_nseconds(average.as_nanos() as u64),
+    );
+
+    if worst > max_latency {
+        anyhow::bail!(
+            "Decoding the frame with PTS {} on {} took {:?}, above {} {}",
+            worst_pts,
+            element_name,
+            worst,
+            value,
+            unit
+        );
+    }
+
+    Ok(())
+}
+
+/// Send EOS to a single element, such as one of the sources of a multi-branch
+/// pipeline, rather than to the whole pipeline.
+#[when(expr = "I send EOS to element {word}")]
+fn send_eos_to_element(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    let element = w.find_element(&element_name)?;
+
+    gst::debug!(CAT, "Sending EOS to {}", element_name);
+    if !element.send_event(gst::event::Eos::new()) {
+        anyhow::bail!("{} refused the EOS event", element_name);
+    }
+
+    Ok(())
 }
 
-#[when(expr = "I set property {word} to {word}")]
-fn set_property(w: &mut World, propname: String, value: String) -> Result<(), anyhow::Error> {
-    let (pspec, obj) = w.find_element_property(&propname)?;
+/// Play the pipeline for the given time and stop it, failing as soon as an
+/// error is posted on the bus while it runs.
+#[when(expr = "I run the pipeline for {word} {word}")]
+async fn run_pipeline_for(w: &mut World, value: u64, unit: String) -> Result<(), anyhow::Error> {
+    let duration = parse_duration(value, &unit)?;
+
+    w.set_pipeline_state("play".to_string()).await?;
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        if let Err(err) = w.check_no_errors() {
+            anyhow::bail!("Pipeline failed after {:?}: {}", start.elapsed(), err);
+        }
+
+        task::sleep(cmp::min(
+            Duration::from_millis(100),
+            duration.saturating_sub(start.elapsed()),
+        ))
+        .await;
+    }
+
+    w.check_no_errors()?;
+    w.set_pipeline_state("stop".to_string()).await
+}
+
+/// Seek the pipeline back to its current position with a flushing seek and
+/// check only the first buffer flowing through the named element afterwards is
+/// flagged DISCONT.
+#[then(expr = "No discontinuity persists after seeking on {word}")]
+async fn check_no_discont_after_seek(
+    w: &mut World,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let pad = w.find_data_pad(&element_name)?;
+
+    // DISCONT flags of the buffers seen since the last flush.
+    let disconts = Arc::new(Mutex::new(Vec::new()));
+    let probe_id = pad
+        .add_probe(
+            gst::PadProbeType::BUFFER | gst::PadProbeType::EVENT_FLUSH,
+            {
+                let disconts = disconts.clone();
+                move |_, info| {
+                    match &info.data {
+                        Some(gst::PadProbeData::Buffer(buffer)) => disconts
+                            .lock()
+                            .unwrap()
+                            .push(buffer.flags().contains(gst::BufferFlags::DISCONT)),
+                        Some(gst::PadProbeData::Event(event))
+                            if event.type_() == gst::EventType::FlushStop =>
+                        {
+                            disconts.lock().unwrap().clear()
+                        }
+                        _ => (),
+                    }
+
+                    gst::PadProbeReturn::Ok
+                }
+            },
+        )
+        .ok_or_else(|| anyhow::anyhow!("Could not add a buffer probe on {}", element_name))?;
+
+    let position = w
+        .get_pipeline()?
+        .query_position::<gst::ClockTime>()
+        .unwrap_or(gst::ClockTime::ZERO);
+    let res = w
+        .seek_and_wait(gst::event::Seek::new(
+            1.,
+            gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+            gst::SeekType::Set,
+            position,
+            gst::SeekType::None,
+            gst::ClockTime::NONE,
+        ))
+        .await;
+
+    let start = Instant::now();
+    while res.is_ok()
+        && disconts.lock().unwrap().len() < PROBED_BUFFERS_COUNT
+        && start.elapsed() < DEFAULT_TIMEOUT
+    {
+        task::sleep(Duration::from_millis(50)).await;
+    }
+    pad.remove_probe(probe_id);
+    res?;
+
+    let disconts = disconts.lock().unwrap();
+    if disconts.len() < 2 {
+        anyhow::bail!(
+            "Only {} buffers seen on {} after the seek",
+            disconts.len(),
+            element_name
+        );
+    }
+
+    let count = disconts.iter().skip(1).filter(|discont| **discont).count();
+    if count > 0 {
+        anyhow::bail!(
+            "{} of the {} buffers following the first one on {} after the seek were flagged DISCONT",
+            count,
+            disconts.len() - 1,
+            element_name
+        );
+    }
 
-    gst::debug!(CAT, "Setting {}={}", propname, value);
-    obj.set_property_from_str(pspec.name(), &value);
     Ok(())
 }
 
-#[then(expr = "Property {word} equals {word}")]
-fn get_property(w: &mut World, propname: String, value: String) -> Result<(), anyhow::Error> {
-    let (pspec, obj) = w.find_element_property(&propname)?;
+/// Check the caps negotiated on the named pad are a subset of the given caps,
+/// e.g. `video/x-raw,format=I420` accepts any I420 raw video caps.
+#[then(expr = "Caps on pad {word} of {word} match {string}")]
+fn check_pad_caps(
+    w: &mut World,
+    pad_name: String,
+    element_name: String,
+    caps: String,
+) -> Result<(), anyhow::Error> {
+    let expected =
+        gst::Caps::from_str(&caps).map_err(|_| anyhow::anyhow!("Invalid caps: {}", caps))?;
+    let current = w
+        .find_pad(&pad_name, &element_name)?
+        .current_caps()
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "No caps negotiated on {}:{} yet, is the pipeline playing?",
+                element_name,
+                pad_name
+            )
+        })?;
+
+    if !current.is_subset(&expected) {
+        anyhow::bail!(
+            "Caps on {}:{} are {} which doesn't match {}",
+            element_name,
+            pad_name,
+            current,
+            expected
+        );
+    }
 
-    // FIXME: Use glib::Value::deserialize_with_pspec() when we can depend on 1.20 API.
-    let v = glib::Value::deserialize(&value, pspec.type_()).unwrap();
-    let obj_value = obj.property_value(pspec.name());
-    debug_assert!(
-        v.compare(&obj_value).unwrap() == cmp::Ordering::Equal,
-        "{}={} != {}",
-        propname,
-        obj_value.serialize().unwrap(),
-        v.serialize().unwrap()
-    );
     Ok(())
 }
 
-#[then(expr = "Validate should not report any issue")]
-#[cfg(feature = "validate")]
-fn validate_no_reports(w: &mut World) -> Result<(), anyhow::Error> {
-    match &w.validate.runner {
-        None => debug_assert!(
-            w.validate.runner.is_some(),
-            "Validate hasn't been activated"
-        ),
-        Some(runner) => debug_assert!(
-            runner.reports_count() == 0,
-            "Reported issues: {}",
-            runner.printf()
-        ),
+/// Run an allocation query downstream of the src pad of the named element,
+/// with its negotiated caps, and check the given meta API, such as
+/// `GstVideoMeta`, is advertised in the answer.
+#[then(expr = "{word} supports the {word} meta via allocation")]
+fn check_allocation_meta(
+    w: &mut World,
+    element_name: String,
+    meta: String,
+) -> Result<(), anyhow::Error> {
+    let pad = w.find_pad("src", &element_name)?;
+    let caps = pad
+        .current_caps()
+        .ok_or_else(|| anyhow::anyhow!("No caps negotiated on {}:src yet", element_name))?;
+
+    let mut query = gst::query::Allocation::new(&caps, false);
+    if !pad.peer_query(&mut query) {
+        anyhow::bail!(
+            "Allocation query not answered downstream of {}",
+            element_name
+        );
+    }
+
+    let metas = query
+        .allocation_metas()
+        .into_iter()
+        .map(|(api, _)| api.name().to_string())
+        .collect::<Vec<_>>();
+    let api = format!("{}API", meta.trim_end_matches("API"));
+    if !metas.contains(&api) {
+        anyhow::bail!(
+            "{} is not advertised downstream of {}, allocation metas: {:?}",
+            api,
+            element_name,
+            metas
+        );
     }
 
     Ok(())
 }
 
-#[given(regex = r"The validate configuration '(.*)'$")]
-#[cfg(feature = "validate")]
-fn add_validate_config(w: &mut World, config: String) {
-    gstvalidate::init();
-    if w.validate.validateconfig.is_none() {
-        w.validate.validateconfig =
-            Some(tempfile::NamedTempFile::new().expect("Could not create temporary file"));
+/// Fields of a `VideoMeta`, copied out of the buffer carrying it so that the
+/// buffer doesn't need to be kept alive.
+#[derive(Debug)]
+struct VideoMetaLayout {
+    format: gstvideo::VideoFormat,
+    width: u32,
+    height: u32,
+    n_planes: u32,
+    strides: Vec<i32>,
+    offsets: Vec<usize>,
+    plane_sizes: Option<Vec<usize>>,
+    buffer_size: usize,
+}
+
+impl VideoMetaLayout {
+    fn new(meta: &gstvideo::VideoMeta, buffer: &gst::BufferRef) -> Self {
+        Self {
+            format: meta.format(),
+            width: meta.width(),
+            height: meta.height(),
+            n_planes: meta.n_planes(),
+            strides: meta.stride().to_vec(),
+            offsets: meta.offset().to_vec(),
+            plane_sizes: meta.plane_size().ok().map(|sizes| sizes.to_vec()),
+            buffer_size: buffer.size(),
+        }
     }
+}
 
-    use std::io::Write;
-    writeln!(w.validate.validateconfig.as_ref().unwrap(), "{}", config)
-        .expect("Couldn't write temporary config");
+/// Check a `VideoMeta` layout is consistent with the negotiated video info:
+/// same format and dimensions, strides large enough to hold a line, and planes
+/// fitting in the buffer.
+fn check_video_meta(
+    meta: &VideoMetaLayout,
+    info: &gstvideo::VideoInfo,
+) -> Result<(), anyhow::Error> {
+    if (meta.format, meta.width, meta.height, meta.n_planes)
+        != (info.format(), info.width(), info.height(), info.n_planes())
+    {
+        anyhow::bail!(
+            "meta describes {:?} {}x{} with {} planes, caps {:?} {}x{} with {} planes",
+            meta.format,
+            meta.width,
+            meta.height,
+            meta.n_planes,
+            info.format(),
+            info.width(),
+            info.height(),
+            info.n_planes()
+        );
+    }
+
+    let plane_sizes = meta
+        .plane_sizes
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("meta has invalid plane sizes"))?;
+    let planes = meta.strides.iter().zip(&meta.offsets).zip(plane_sizes);
+    for (plane, ((stride, offset), plane_size)) in planes.enumerate() {
+        let min_stride = info.stride()[plane];
+        if stride.abs() < min_stride.abs() {
+            anyhow::bail!(
+                "stride of plane {} is {}, below the {} bytes of a line",
+                plane,
+                stride,
+                min_stride
+            );
+        }
+
+        let end = offset + plane_size;
+        if end > meta.buffer_size {
+            anyhow::bail!(
+                "plane {} ends at offset {}, past the {} bytes of the buffer",
+                plane,
+                end,
+                meta.buffer_size
+            );
+        }
+    }
+
+    Ok(())
 }
 
-#[given(expr = "Validate is activated")]
-#[cfg(feature = "validate")]
-fn activate_validate(w: &mut World) -> Result<(), anyhow::Error> {
-    debug_assert!(
-        w.validate.runner.is_none(),
-        "Validate has already been activated"
-    );
+/// Check the `VideoMeta` carried by buffers flowing through the named element,
+/// describing strides and plane offsets for zero-copy paths, is consistent
+/// with the negotiated caps.
+#[then(expr = "The video frames on {word} carry consistent VideoMeta")]
+async fn check_video_metas(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    let pad = w.find_data_pad(&element_name)?;
 
-    if let Some(validateconfig) = w.validate.validateconfig.take() {
-        let config_temp_path = validateconfig.into_temp_path();
-        let path = config_temp_path
-            .as_os_str()
-            .to_str()
-            .expect("Invalid config temporary file")
-            .to_string();
-        gst::debug!(CAT, "Got config: {}", &path);
-        config_temp_path.keep().expect("Could not keep config");
+    let metas = probe_pad(&pad, PROBED_BUFFERS_COUNT, DEFAULT_TIMEOUT, |buffer, _| {
+        buffer
+            .meta::<gstvideo::VideoMeta>()
+            .map(|meta| VideoMetaLayout::new(&meta, buffer))
+    })
+    .await?;
+    let caps = pad
+        .current_caps()
+        .ok_or_else(|| anyhow::anyhow!("No caps negotiated on {} yet", element_name))?;
+    let info = gstvideo::VideoInfo::from_caps(&caps)
+        .map_err(|_| anyhow::anyhow!("Caps on {} are not video caps: {}", element_name, caps))?;
 
-        std::env::set_var("GST_VALIDATE_CONFIG", path);
+    let mut with_meta = 0;
+    for (index, meta) in metas.iter().enumerate() {
+        if let Some(meta) = meta {
+            with_meta += 1;
+            check_video_meta(meta, &info).map_err(|err| {
+                anyhow::anyhow!(
+                    "Inconsistent VideoMeta on buffer {} on {}: {}",
+                    index,
+                    element_name,
+                    err
+                )
+            })?;
+        }
+    }
+
+    if with_meta == 0 {
+        anyhow::bail!(
+            "None of the {} buffers seen on {} carried a VideoMeta",
+            metas.len(),
+            element_name
+        );
     }
 
-    gstvalidate::init();
-    let runner = gstvalidate::Runner::new();
-    let _ = w.validate.runner.insert(runner.clone());
-    let pipeline = w.get_pipeline()?;
-    w.validate.monitor = Some(gstvalidate::Monitor::factory_create(
-        pipeline.upcast_ref::<gst::Object>(),
-        &runner,
-        gstvalidate::Monitor::NONE,
-    ));
     Ok(())
 }
 
-#[when(expr = "I {word} the pipeline")]
-pub fn set_state(w: &mut World, state: String) -> Result<(), anyhow::Error> {
-    w.set_pipeline_state(state)
+/// Make the named element, e.g. `identity` or `fakesrc`, return EOS after
+/// `PROBED_BUFFERS_COUNT` buffers, through its `eos-after` property unless
+/// already set, and check the pipeline reaches EOS and stops without error.
+#[then(expr = "The pipeline finishes cleanly when {word} returns EOS early")]
+async fn check_early_eos(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
+    let element = w.find_element(&element_name)?;
+    if element.find_property("eos-after").is_none() {
+        anyhow::bail!("{} has no eos-after property", element_name);
+    }
+    if element.property_value("eos-after").get::<i32>().ok() == Some(-1) {
+        element.set_property("eos-after", PROBED_BUFFERS_COUNT as i32);
+    }
+
+    w.set_pipeline_state("play".to_string()).await?;
+    let msg = w
+        .wait_for_message(DEFAULT_TIMEOUT, |msg| {
+            matches!(
+                msg.view(),
+                gst::MessageView::Eos(..) | gst::MessageView::Error(..)
+            )
+        })
+        .await
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "Pipeline did not reach EOS {} seconds after {} returned EOS",
+                DEFAULT_TIMEOUT.as_secs(),
+                element_name
+            )
+        })?;
+    if let gst::MessageView::Error(err) = msg.view() {
+        anyhow::bail!(
+            "Error from {:?} after {} returned EOS: {} ({:?})",
+            err.src().map(|s| s.path_string()),
+            element_name,
+            err.error(),
+            err.debug()
+        );
+    }
+
+    w.set_pipeline_state("stop".to_string()).await
 }
 
-fn get_last_frame(w: &World, element_name: &str) -> Result<Option<gst::Sample>, anyhow::Error> {
+/// Save the last frame rendered by the named sink as a PNG image, relative to
+/// the feature file when the path is relative, for instance to review what a
+/// failing scenario displayed. The sink needs `enable-last-sample` set.
+#[when(expr = "Save frame from {word} to {string}")]
+fn save_frame(w: &mut World, element_name: String, path: String) -> Result<(), anyhow::Error> {
+    let path = w.resolve_path(&path);
+    let sample = get_last_frame(w, &element_name)?
+        .ok_or_else(|| anyhow::anyhow!("{} did not render any frame yet", element_name))?;
+
+    let png = gstvideo::convert_sample(
+        &sample,
+        &gst::Caps::new_empty_simple("image/png"),
+        gst::ClockTime::from_nseconds(DEFAULT_TIMEOUT.as_nanos() as u64),
+    )?;
+    let buffer = png
+        .buffer()
+        .ok_or_else(|| anyhow::anyhow!("Converted frame has no buffer"))?
+        .map_readable()?;
+    std::fs::write(&path, buffer.as_slice())
+        .map_err(|err| anyhow::anyhow!("Could not write {}: {}", path.display(), err))?;
+
+    gst::debug!(
+        CAT,
+        "Saved frame from {} to {}",
+        element_name,
+        path.display()
+    );
+    Ok(())
+}
+
+/// Check no error originating from the element owning the named pad, or from
+/// its children, gets posted on the bus for `DEFAULT_TIMEOUT`. Flow returns of
+/// pad pushes can't be observed from pad probes, so flow errors are only caught
+/// when the element reports them on the bus, which streaming threads do for
+/// fatal flow returns such as `not-negotiated`. Errors reported by the upstream
+/// element driving the streaming thread are not taken into account.
+#[then(expr = "No flow errors occur on the {word} pad of {word}")]
+async fn check_no_flow_errors(
+    w: &mut World,
+    pad_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
     let element = w
-        .get_pipeline()?
-        .downcast_ref::<gst::Bin>()
-        .unwrap()
-        .by_name_recurse_up(element_name)
-        .ok_or_else(|| anyhow::anyhow!("Could not find element: {}", element_name))?;
+        .find_pad(&pad_name, &element_name)?
+        .parent_element()
+        .unwrap();
+
+    let msg = w
+        .wait_for_message(DEFAULT_TIMEOUT, |msg| {
+            matches!(msg.view(), gst::MessageView::Error(..))
+                && msg
+                    .src()
+                    .is_some_and(|src| src == element || src.has_as_ancestor(&element))
+        })
+        .await;
+    if let Some(msg) = msg {
+        if let gst::MessageView::Error(err) = msg.view() {
+            anyhow::bail!(
+                "Error from {:?} on {}:{}: {} ({:?})",
+                err.src().map(|s| s.path_string()),
+                element_name,
+                pad_name,
+                err.error(),
+                err.debug()
+            );
+        }
+    }
 
-    get_last_frame_on_element(&element)
+    Ok(())
 }
 
-/// Retrieve the most recent gst::Sample from the given video sink. We assume
-/// the `enable-last-sample` property is enabled on this element.
-pub fn get_last_frame_on_element(
-    element: &gst::Element,
-) -> Result<Option<gst::Sample>, anyhow::Error> {
-    let enable_last_sample = if element.find_property("enable-last-sample").is_some() {
-        element.property::<bool>("enable-last-sample")
-    } else {
-        gst::error!(CAT, "Sink doesn't have a `enable-last-sample' property");
-        false
+/// Check the rate of the last segment event that went through the named pad,
+/// e.g. after a trick-mode seek, waiting for the new segment to propagate.
+#[then(expr = "The segment rate on the {word} pad of {word} is {word}")]
+async fn check_segment_rate(
+    w: &mut World,
+    pad_name: String,
+    element_name: String,
+    rate: String,
+) -> Result<(), anyhow::Error> {
+    let expected =
+        f64::from_str(&rate).map_err(|_| anyhow::anyhow!("Invalid playback rate: {}", rate))?;
+    let pad = w.find_pad(&pad_name, &element_name)?;
+
+    let start = Instant::now();
+    loop {
+        let actual = pad
+            .sticky_event::<gst::event::Segment>(0)
+            .map(|event| event.segment().rate());
+        if let Some(actual) = actual {
+            if (actual - expected).abs() < 1e-6 {
+                return Ok(());
+            }
+        }
+
+        if start.elapsed() >= DEFAULT_TIMEOUT {
+            anyhow::bail!(
+                "Segment rate on {}:{} is {:?} instead of {} after {} seconds",
+                element_name,
+                pad_name,
+                actual,
+                expected,
+                DEFAULT_TIMEOUT.as_secs()
+            );
+        }
+
+        task::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+/// Check the value of a tag posted on the bus, e.g. `Tag title equals "Foo"`.
+/// Tags posted by all the elements are merged, the most recent value of a tag
+/// taking precedence. Non-string values are compared in their serialized form.
+#[then(expr = "Tag {word} equals {string}")]
+fn check_tag(w: &mut World, tag_name: String, expected: String) -> Result<(), anyhow::Error> {
+    let tags = w.tags.lock().unwrap();
+
+    let value = tags.generic(&tag_name).ok_or_else(|| {
+        let seen = (0..tags.n_tags())
+            .filter_map(|idx| tags.nth_tag_name(idx))
+            .collect::<Vec<_>>();
+        anyhow::anyhow!("Tag {} not posted, tags seen: {:?}", tag_name, seen)
+    })?;
+    let actual = match value.get::<String>() {
+        Ok(actual) => actual,
+        Err(_) => value
+            .serialize()
+            .map(|s| s.to_string())
+            .map_err(|_| anyhow::anyhow!("Could not serialize tag {}: {:?}", tag_name, value))?,
     };
 
-    if !enable_last_sample {
-        anyhow::bail!("Property `enable-last-sample` not `true` on: {} - you need to set it when defining the pipeline", element.name());
+    if actual != expected {
+        anyhow::bail!("Tag {} is '{}' instead of '{}'", tag_name, actual, expected);
     }
 
-    Ok(element.property::<Option<gst::Sample>>("last-sample"))
+    Ok(())
 }
 
-#[then(expr = "The user can see a frame on {word}")]
-async fn check_last_frame(w: &mut World, element_name: String) -> Result<(), anyhow::Error> {
-    let _ = w.get_pipeline()?.state(gst::ClockTime::NONE);
-    let timeout = Duration::from_secs(5);
+/// Emit the action signal `signal_name` on the named element, with arguments
+/// deserialized from their comma-separated string representations according to
+/// the signal parameter types.
+fn emit_signal(
+    w: &World,
+    signal_name: &str,
+    element_name: &str,
+    args: &[&str],
+) -> Result<(), anyhow::Error> {
+    let element = w.find_element(element_name)?;
+    let signal_id =
+        glib::subclass::SignalId::lookup(signal_name, element.type_()).ok_or_else(|| {
+            anyhow::anyhow!("Element {} has no signal '{}'", element_name, signal_name)
+        })?;
 
-    let start = SystemTime::now();
-    loop {
-        if get_last_frame(w, &element_name)?.is_some() {
-            return Ok(());
+    let query = signal_id.query();
+    if query.param_types().len() != args.len() {
+        anyhow::bail!(
+            "Signal '{}' on {} takes {} arguments, {} given",
+            signal_name,
+            element_name,
+            query.param_types().len(),
+            args.len()
+        );
+    }
+
+    let values = query
+        .param_types()
+        .iter()
+        .zip(args)
+        .map(|(type_, arg)| {
+            let type_ = glib::Type::from(*type_);
+            glib::Value::deserialize(arg, type_)
+                .map_err(|_| anyhow::anyhow!("Invalid {} argument: {}", type_, arg))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    gst::debug!(CAT, "Emitting '{}' on {}", signal_name, element_name);
+    let ret = element.emit_by_name_with_values(signal_name, &values);
+    gst::debug!(
+        CAT,
+        "'{}' on {} returned {:?}",
+        signal_name,
+        element_name,
+        ret
+    );
+
+    Ok(())
+}
+
+#[when(expr = "I emit signal {word} on {word}")]
+fn emit_action_signal(
+    w: &mut World,
+    signal_name: String,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    emit_signal(w, &signal_name, &element_name, &[])
+}
+
+/// Same as above with comma-separated arguments, e.g.
+/// `I emit signal get-video-pad on playbin with "0"`.
+#[when(expr = "I emit signal {word} on {word} with {string}")]
+fn emit_action_signal_with_args(
+    w: &mut World,
+    signal_name: String,
+    element_name: String,
+    args: String,
+) -> Result<(), anyhow::Error> {
+    let args = args.split(',').map(str::trim).collect::<Vec<_>>();
+    emit_signal(w, &signal_name, &element_name, &args)
+}
+
+/// Set properties of the playing pipeline from concurrent tasks, each property
+/// being set `CONCURRENT_PROPERTY_SETS` times, stress-testing the thread-safety
+/// of runtime property changes. Properties are listed in a data table, e.g.:
+///
+/// ```gherkin
+/// When I concurrently set while playing:
+///   | volume::volume | 0.5  |
+///   | volume::mute   | true |
+/// ```
+#[when(expr = "I concurrently set while playing:")]
+async fn set_properties_concurrently(
+    w: &mut World,
+    step: &cucumber::gherkin::Step,
+) -> Result<(), anyhow::Error> {
+    let table = step
+        .table
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Missing properties data table"))?;
+
+    let (_, current, _) = w.get_pipeline()?.state(gst::ClockTime::ZERO);
+    if current != gst::State::Playing {
+        anyhow::bail!("Pipeline is {:?}, not PLAYING", current);
+    }
+
+    let mut setters = Vec::new();
+    for row in table.rows.iter() {
+        let (propname, value) = match row.as_slice() {
+            [propname, value] => (propname.clone(), value.clone()),
+            _ => anyhow::bail!("Invalid property row: {:?}, expected property | value", row),
+        };
+
+        let (pspec, obj) = w.find_element_property(&propname)?;
+        if glib::Value::deserialize(&value, pspec.value_type()).is_err() {
+            anyhow::bail!(
+                "Invalid value for {} of type {}: {}",
+                propname,
+                pspec.value_type(),
+                value
+            );
         }
 
-        task::sleep(Duration::from_millis(500)).await;
-        if let Ok(elapsed) = start.elapsed() {
-            if elapsed >= timeout {
-                anyhow::bail!(
-                    "Timeout reached, video sink still not pre-rolled after {} seconds",
-                    timeout.as_secs()
-                );
+        // Setters run on other threads, only GstObjects are thread-safe.
+        let obj = obj.downcast::<gst::Object>().map_err(|obj| {
+            anyhow::anyhow!(
+                "Property {} belongs to a {}, not to a GstObject",
+                propname,
+                obj.type_()
+            )
+        })?;
+        setters.push(task::spawn(async move {
+            gst::debug!(CAT, "Concurrently setting {}={}", propname, value);
+            for _ in 0..CONCURRENT_PROPERTY_SETS {
+                obj.set_property_from_str(pspec.name(), &value);
+                task::yield_now().await;
             }
+        }));
+    }
+
+    futures::future::join_all(setters).await;
+    w.check_no_errors()
+}
+
+/// Check the pipeline is still playing and no error was posted on its bus.
+#[then(expr = "The pipeline remains error-free")]
+fn check_error_free(w: &mut World) -> Result<(), anyhow::Error> {
+    w.check_no_errors()?;
+
+    let (_, current, _) = w.get_pipeline()?.state(gst::ClockTime::ZERO);
+    if current != gst::State::Playing {
+        anyhow::bail!("Pipeline is {:?}, not PLAYING anymore", current);
+    }
+
+    Ok(())
+}
+
+/// Parse a state name, `null`, `ready`, `paused` or `playing`.
+fn parse_state(state: &str) -> Result<gst::State, anyhow::Error> {
+    match state.to_lowercase().as_str() {
+        "null" => Ok(gst::State::Null),
+        "ready" => Ok(gst::State::Ready),
+        "paused" => Ok(gst::State::Paused),
+        "playing" => Ok(gst::State::Playing),
+        _ => anyhow::bail!(
+            "Invalid state name: {} only [null, ready, paused, playing] are supported",
+            state
+        ),
+    }
+}
+
+/// Check the current state of a single element, without waiting for pending
+/// state changes to complete.
+#[then(expr = "Element {word} is in state {word}")]
+fn check_element_state(
+    w: &mut World,
+    element_name: String,
+    state: String,
+) -> Result<(), anyhow::Error> {
+    let expected = parse_state(&state)?;
+    let (_, current, pending) = w.find_element(&element_name)?.state(gst::ClockTime::ZERO);
+
+    if current != expected {
+        anyhow::bail!(
+            "{} is in state {:?} (pending {:?}) instead of {:?}",
+            element_name,
+            current,
+            pending,
+            expected
+        );
+    }
+
+    Ok(())
+}
+
+/// Compare the last frame rendered by the named sink with each of the
+/// comma-separated reference images, relative to the feature file when
+/// relative, passing if any of them has a PSNR of at least 35 dB. Useful when
+/// rendering differs slightly between platforms. The sink needs
+/// `enable-last-sample` set.
+#[then(regex = r"The last frame on (\S+) matches any of '(.*)'$")]
+fn check_last_frame_matches_any(
+    w: &mut World,
+    element_name: String,
+    references: String,
+) -> Result<(), anyhow::Error> {
+    let sample = get_last_frame(w, &element_name)?
+        .ok_or_else(|| anyhow::anyhow!("{} did not render any frame yet", element_name))?;
+
+    let mut best = None::<(f64, PathBuf)>;
+    for reference in references.split(',').map(str::trim) {
+        let path = w.resolve_path(reference);
+        let psnr = frame_psnr(&sample, &load_reference_frame(&path)?).map_err(|err| {
+            anyhow::anyhow!(
+                "Last frame on {} and {}: {}",
+                element_name,
+                path.display(),
+                err
+            )
+        })?;
+
+        gst::debug!(
+            CAT,
+            "PSNR of the last frame on {} against {}: {}",
+            element_name,
+            path.display(),
+            psnr
+        );
+        if psnr >= MIN_PSNR {
+            return Ok(());
+        }
+        if best.as_ref().is_none_or(|(best, _)| psnr > *best) {
+            best = Some((psnr, path));
+        }
+    }
+
+    match best {
+        Some((psnr, path)) => anyhow::bail!(
+            "Last frame on {} matches none of the references, best match is {} with a PSNR of {:.2} dB",
+            element_name,
+            path.display(),
+            psnr
+        ),
+        None => anyhow::bail!("No reference frame given"),
+    }
+}
+
+/// Play the pipeline and count the buffers flowing through the named element
+/// until it receives EOS, checking the exact total, e.g. for
+/// `videotestsrc num-buffers=N`. The pipeline needs not to be playing yet so
+/// that no buffer is missed. Fails if the flow stalls for `DEFAULT_TIMEOUT`
+/// before EOS.
+#[then(expr = "Exactly {int} buffers passed {word} before EOS")]
+async fn check_total_buffers(
+    w: &mut World,
+    expected: usize,
+    element_name: String,
+) -> Result<(), anyhow::Error> {
+    let pad = w.find_data_pad(&element_name)?;
+
+    // Number of buffers seen by each probe call, `None` for EOS.
+    let (sender, mut receiver) = mpsc::unbounded();
+    let probe_id = pad
+        .add_probe(
+            gst::PadProbeType::BUFFER
+                | gst::PadProbeType::BUFFER_LIST
+                | gst::PadProbeType::EVENT_DOWNSTREAM,
+            move |_, info| {
+                match &info.data {
+                    Some(gst::PadProbeData::Buffer(_)) => {
+                        let _ = sender.unbounded_send(Some(1));
+                    }
+                    Some(gst::PadProbeData::BufferList(list)) => {
+                        let _ = sender.unbounded_send(Some(list.len()));
+                    }
+                    Some(gst::PadProbeData::Event(event))
+                        if event.type_() == gst::EventType::Eos =>
+                    {
+                        let _ = sender.unbounded_send(None);
+                    }
+                    _ => (),
+                }
+
+                gst::PadProbeReturn::Ok
+            },
+        )
+        .ok_or_else(|| anyhow::anyhow!("Could not add a buffer probe on {}", element_name))?;
+
+    let res = w.set_pipeline_state("play".to_string()).await;
+    let mut seen = 0;
+    let mut eos = false;
+    while res.is_ok() && !eos {
+        match future::timeout(DEFAULT_TIMEOUT, receiver.next()).await {
+            Ok(Some(Some(n_buffers))) => seen += n_buffers,
+            Ok(Some(None)) => eos = true,
+            _ => break,
         }
     }
+    pad.remove_probe(probe_id);
+    res?;
+
+    if !eos {
+        anyhow::bail!(
+            "{} did not receive EOS, {} buffers passed before data stopped flowing for {} seconds",
+            element_name,
+            seen,
+            DEFAULT_TIMEOUT.as_secs()
+        );
+    }
+
+    if seen != expected {
+        anyhow::bail!(
+            "{} buffers passed {} before EOS instead of {}",
+            seen,
+            element_name,
+            expected
+        );
+    }
+
+    Ok(())
+}
+
+/// Check the description the pipeline was created from contains the given
+/// text, e.g. to make sure a description loaded from a file is the expected one.
+#[then(regex = r"The pipeline description contains '(.*)'$")]
+fn check_pipeline_description(w: &mut World, text: String) -> Result<(), anyhow::Error> {
+    let description = w
+        .pipeline_description
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Pipeline not created from a description"))?;
+
+    if !description.contains(&text) {
+        anyhow::bail!(
+            "Pipeline description '{}' doesn't contain '{}'",
+            description,
+            text
+        );
+    }
+
+    Ok(())
+}
+
+/// Push a buffer of the given size to the named `appsrc`, filled with
+/// incrementing bytes. Use `I send EOS to element` to end the stream.
+#[when(expr = "I push buffer of {word} bytes to {word}")]
+fn push_buffer(w: &mut World, size: usize, element_name: String) -> Result<(), anyhow::Error> {
+    let appsrc = w.find_element(&element_name)?;
+    let is_appsrc = glib::Type::from_name("GstAppSrc")
+        .is_some_and(|appsrc_type| appsrc.type_().is_a(appsrc_type));
+    if !is_appsrc {
+        anyhow::bail!("{} is not an appsrc", element_name);
+    }
+
+    let buffer = gst::Buffer::from_mut_slice((0..size).map(|i| i as u8).collect::<Vec<_>>());
+    gst::debug!(CAT, "Pushing buffer of {} bytes to {}", size, element_name);
+    let ret = appsrc.emit_by_name::<gst::FlowReturn>("push-buffer", &[&buffer]);
+    if ret != gst::FlowReturn::Ok {
+        anyhow::bail!("{} refused the buffer: {:?}", element_name, ret);
+    }
+
+    Ok(())
+}
+
+/// Check the number of source elements of the pipeline, elements classified as
+/// sources or only having src pads. Bins are not taken into account, only the
+/// elements they contain.
+#[then(expr = "The pipeline has {int} source elements")]
+fn check_source_count(w: &mut World, expected: usize) -> Result<(), anyhow::Error> {
+    let sources = w
+        .pipeline_elements()?
+        .into_iter()
+        .filter(|element| !element.is::<gst::Bin>())
+        .filter(|element| {
+            element
+                .factory()
+                .is_some_and(|factory| factory.klass().contains("Source"))
+                || (element.sink_pads().is_empty() && !element.src_pads().is_empty())
+        })
+        .map(|element| element.name().to_string())
+        .collect::<Vec<_>>();
+
+    if sources.len() != expected {
+        anyhow::bail!(
+            "Pipeline has {} source elements instead of {}: {:?}",
+            sources.len(),
+            expected,
+            sources
+        );
+    }
+
+    Ok(())
 }
 
 // Re-export all the traits in a prelude module, so that applications
 // can always "use gstreamer_cucumber::prelude::*" without getting conflicts
 pub mod prelude {
-    pub use crate::{get_last_frame_on_element, World};
+    pub use crate::{get_last_frame_on_element, DefaultCucumber, World};
     pub use cucumber::*;
     pub use glib;
     #[doc(hidden)]
     pub use gst::prelude::*;
     pub use gstreamer as gst;
     pub use gstreamer_video as gstvideo;
+
+    #[cfg(feature = "audio")]
+    pub use gstreamer_audio as gstaudio;
+
+    #[cfg(feature = "validate")]
+    pub use gstreamer_validate as gstvalidate;
 }
@@ -1,9 +1,123 @@
-use glib;
 use gstreamer_cucumber::World;
 
+/// Example of test-only element: a sink bin discarding everything it receives.
+/// Its `critical-on-start` property makes it log a GLib critical when
+/// starting, to check criticals get caught.
+mod testsink {
+    use gstreamer as gst;
+    use gstreamer::glib;
+    use gstreamer::prelude::*;
+    use gstreamer::subclass::prelude::*;
+    use once_cell::sync::Lazy;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    mod imp {
+        use super::*;
+        // The glib version of gstreamer, rather than the one of the test binary.
+        use super::glib;
+
+        #[derive(Default)]
+        pub struct TestSink {
+            critical_on_start: AtomicBool,
+        }
+
+        #[glib::object_subclass]
+        impl ObjectSubclass for TestSink {
+            const NAME: &'static str = "GstCucumberTestSink";
+            type Type = super::TestSink;
+            type ParentType = gst::Bin;
+        }
+
+        impl ObjectImpl for TestSink {
+            fn properties() -> &'static [glib::ParamSpec] {
+                static PROPERTIES: Lazy<Vec<glib::ParamSpec>> = Lazy::new(|| {
+                    vec![glib::ParamSpecBoolean::builder("critical-on-start")
+                        .nick("Critical on start")
+                        .blurb("Log a GLib critical when going from READY to PAUSED")
+                        .build()]
+                });
+
+                PROPERTIES.as_ref()
+            }
+
+            fn set_property(&self, _id: usize, value: &glib::Value, pspec: &glib::ParamSpec) {
+                match pspec.name() {
+                    "critical-on-start" => self
+                        .critical_on_start
+                        .store(value.get().unwrap(), Ordering::SeqCst),
+                    _ => unimplemented!(),
+                }
+            }
+
+            fn property(&self, _id: usize, pspec: &glib::ParamSpec) -> glib::Value {
+                match pspec.name() {
+                    "critical-on-start" => self.critical_on_start.load(Ordering::SeqCst).to_value(),
+                    _ => unimplemented!(),
+                }
+            }
+
+            fn constructed(&self) {
+                self.parent_constructed();
+
+                let bin = self.obj();
+                let sink = gst::ElementFactory::make("fakesink").build().unwrap();
+                bin.add(&sink).unwrap();
+                let pad =
+                    gst::GhostPad::with_target(Some("sink"), &sink.static_pad("sink").unwrap())
+                        .unwrap();
+                bin.add_pad(&pad).unwrap();
+            }
+        }
+
+        impl GstObjectImpl for TestSink {}
+
+        impl ElementImpl for TestSink {
+            fn metadata() -> Option<&'static gst::subclass::ElementMetadata> {
+                static ELEMENT_METADATA: Lazy<gst::subclass::ElementMetadata> = Lazy::new(|| {
+                    gst::subclass::ElementMetadata::new(
+                        "Cucumber test sink",
+                        "Sink",
+                        "Discards everything it receives",
+                        "Philippe Normand <philn@igalia.com>",
+                    )
+                });
+
+                Some(&*ELEMENT_METADATA)
+            }
+
+            fn change_state(
+                &self,
+                transition: gst::StateChange,
+            ) -> Result<gst::StateChangeSuccess, gst::StateChangeError> {
+                if transition == gst::StateChange::ReadyToPaused
+                    && self.critical_on_start.load(Ordering::SeqCst)
+                {
+                    glib::g_critical!("cucumbertestsink", "Starting with critical-on-start set");
+                }
+
+                self.parent_change_state(transition)
+            }
+        }
+
+        impl BinImpl for TestSink {}
+    }
+
+    glib::wrapper! {
+        pub struct TestSink(ObjectSubclass<imp::TestSink>) @extends gst::Bin, gst::Element, gst::Object, @implements gst::ChildProxy;
+    }
+}
+
 async fn async_main() -> Result<(), anyhow::Error> {
     gstreamer::init()?;
+    World::register_element::<testsink::TestSink>("cucumbertestsink", gstreamer::Rank::None)?;
     World::run("tests/features/basic.feature", None).await;
+    World::run("tests/features/steps.feature", None).await;
+
+    // Each scenario of this feature fails on exactly one of its steps.
+    match World::run_checked("tests/features/failures.feature", None).await {
+        Err(err) if err.to_string() == "17 steps failed, 0 parsing errors, 0 hook errors" => (),
+        res => anyhow::bail!("Unexpected result of the failing scenarios: {:?}", res),
+    }
     Ok(())
 }
 
@@ -0,0 +1,74 @@
+@should-fail
+Feature: Failing steps
+
+  Each scenario is expected to fail on exactly one step, without aborting the
+  following scenarios.
+
+  Scenario: setting a property of a missing element fails
+    Given Pipeline is 'videotestsrc name=src num-buffers=1 ! fakesink name=sink'
+    When I set property nosuchelement::num-buffers to 1
+
+  Scenario: setting a missing property fails
+    Given Pipeline is 'videotestsrc name=src num-buffers=1 ! fakesink name=sink'
+    When I set property src::no-such-property to 1
+
+  Scenario: checking a property with the wrong value fails
+    Given Pipeline is 'videotestsrc name=src num-buffers=1 ! fakesink name=sink'
+    Then Property src::num-buffers equals 2
+
+  Scenario: logging a GLib critical fails the check
+    Given Pipeline is 'videotestsrc num-buffers=1 ! cucumbertestsink critical-on-start=true'
+    Then The pipeline reached EOS within 5sec
+    Then No GLib criticals are logged
+
+  Scenario Outline: comparing a property to a value fails
+    Given Pipeline is 'videotestsrc name=src num-buffers=5 ! fakesink name=sink'
+    Then Property src::num-buffers <relation> <value>
+
+    Examples:
+      | relation        | value |
+      | is greater than | 5     |
+      | is less than    | 5     |
+      | is at least     | 6     |
+      | is at most      | 4     |
+
+  Scenario: loading a missing pipeline file fails
+    Given Pipeline from file "no-such-file.pipeline"
+
+  Scenario: a description not containing the text fails
+    Given Pipeline is 'videotestsrc num-buffers=1 ! fakesink name=sink'
+    Then The pipeline description contains 'audiotestsrc'
+
+  Scenario: a wrong buffer count fails
+    Given Pipeline is 'videotestsrc num-buffers=3 ! fakesink name=sink'
+    Then Exactly 2 buffers passed sink before EOS
+
+  Scenario: a wrong buffer checksum fails
+    Given Pipeline is 'fakesrc filltype=zero sizetype=fixed sizemax=16 ! fakesink name=sink'
+    When I play the pipeline
+    Then The checksum of the next buffer on sink is '0000000000000000000000000000000000000000000000000000000000000000'
+
+  Scenario: a wrong position fails
+    Given Pipeline is 'videotestsrc ! fakesink name=sink'
+    When I pause the pipeline
+    When I seek to 2sec
+    Then Pipeline position is 10sec
+
+  Scenario: seeking a stopped pipeline fails
+    Given Pipeline is 'videotestsrc ! fakesink name=sink'
+    When I seek to 2sec
+
+  Scenario: sending EOS to a missing element fails
+    Given Pipeline is 'videotestsrc num-buffers=1 ! fakesink name=sink'
+    When I send EOS to element nosuchelement
+
+  Scenario: setting a structure property of another type fails
+    Given Pipeline is 'videotestsrc name=src num-buffers=1 ! fakesink name=sink'
+    When I set structure property src::num-buffers to 'application/x-test'
+
+  Scenario: setting caps with an invalid field type fails
+    Given Pipeline is 'videotestsrc num-buffers=1 ! capsfilter name=filter ! fakesink'
+    When I set caps on filter from:
+      | field | type    | value       |
+      | name  | string  | video/x-raw |
+      | width | integer | 320         |
@@ -0,0 +1,79 @@
+Feature: Built-in steps
+
+  Scenario: invalid property values are rejected
+    Given Pipeline is 'videotestsrc name=src num-buffers=1 ! fakesink name=sink'
+    Then Setting property src::num-buffers to -5 is rejected
+    Then Setting property src::num-buffers to foo is rejected
+
+  Scenario: request pads linked in the pipeline description are kept
+    Given Pipeline is 'videotestsrc num-buffers=1 ! tee name=t ! fakesink'
+    Then The pipeline reached EOS within 5sec
+    Then No request pads remain after stopping t
+
+  Scenario: test-only elements can be used in pipelines
+    Given Element cucumbertestsink is registered
+    Given Pipeline is 'videotestsrc num-buffers=1 ! cucumbertestsink name=sink'
+    Then The pipeline reached EOS within 5sec
+
+  Scenario: GLib criticals are recorded when checked
+    Given Pipeline is 'videotestsrc num-buffers=1 ! fakesink'
+    Then The pipeline reached EOS within 5sec
+    Then No GLib criticals are logged
+
+  Scenario: buffers can be pushed to an appsrc
+    Given Pipeline is 'appsrc name=src ! fakesink name=sink'
+    When I play the pipeline
+    When I push buffer of 16 bytes to src
+    Then All sinks receive EOS within 5 sec
+
+  Scenario Outline: properties can be compared to a value
+    Given Pipeline is 'videotestsrc name=src num-buffers=5 ! fakesink name=sink'
+    Then Property src::num-buffers <relation> <value>
+
+    Examples:
+      | relation        | value |
+      | is greater than | 4     |
+      | is less than    | 6     |
+      | is at least     | 5     |
+      | is at most      | 5     |
+
+  Scenario: pipelines can be loaded from a file
+    Given Pipeline from file "videotestsrc.pipeline"
+    Then The pipeline description contains 'fakesink name=sink'
+    Then The pipeline reached EOS within 5sec
+
+  Scenario: the exact number of buffers is counted
+    Given Pipeline is 'videotestsrc num-buffers=3 ! fakesink name=sink'
+    Then Exactly 3 buffers passed sink before EOS
+
+  Scenario: buffer checksums are compared
+    Given Pipeline is 'fakesrc filltype=zero sizetype=fixed sizemax=16 ! fakesink name=sink'
+    When I play the pipeline
+    Then The checksum of the next buffer on sink is '374708fff7719dd5979ec875d56cd2286f6d3cf7ec317a3b25632aab28ec37bb'
+
+  Scenario: the position is reported after seeking
+    Given Pipeline is 'videotestsrc ! fakesink name=sink'
+    When I pause the pipeline
+    When I seek to 2sec
+    Then Pipeline position is 2sec
+
+  Scenario: EOS can be sent to a single element
+    Given Pipeline is 'videotestsrc name=src ! video/x-raw,framerate=1/1 ! fakesink sync=true'
+    When I play the pipeline
+    When I send EOS to element src
+    Then The pipeline reached EOS within 5sec
+
+  Scenario: structure properties can be set
+    Given Pipeline is 'rtpbin name=rtp videotestsrc num-buffers=1 ! fakesink'
+    When I set structure property rtp::sdes to 'application/x-rtp-source-sdes, cname=(string)cucumber'
+    Then The pipeline reached EOS within 5sec
+
+  Scenario: caps can be set from a data table
+    Given Pipeline is 'videotestsrc num-buffers=1 ! capsfilter name=filter ! fakesink'
+    When I set caps on filter from:
+      | field     | type     | value       |
+      | name      | string   | video/x-raw |
+      | width     | int      | 320         |
+      | height    | int      | 240         |
+      | framerate | fraction | 30/1        |
+    Then The pipeline reached EOS within 5sec